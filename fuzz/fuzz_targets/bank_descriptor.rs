@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mpk_mini_ctl::MpkBankDescriptor;
+
+fuzz_target!(|bytes: &[u8]| {
+    // Anything parsed must encode back to the same bytes, unless it is not accepted by the device
+    if let Ok(bank_desc) = MpkBankDescriptor::from(bytes) {
+        if let Ok(encoded) = bank_desc.clone().into_bytes() {
+            assert_eq!(bank_desc, MpkBankDescriptor::from(&encoded).unwrap());
+        }
+    }
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mpk_mini_ctl::MpkMidiMessage;
+
+fuzz_target!(|bytes: &[u8]| {
+    let _ = MpkMidiMessage::parse_msg(bytes);
+});
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mpk_mini_ctl::mpkbank::MiddleC;
+use mpk_mini_ctl::patch::BankPatch;
+use mpk_mini_ctl::validate::validate_preset;
+use mpk_mini_ctl::Preset;
+
+fuzz_target!(|bytes: &[u8]| {
+    let _ = Preset::from_reader(bytes);
+    let _ = BankPatch::from_reader(bytes, MiddleC::C4);
+    if let Ok(value) = serde_yaml::from_slice(bytes) {
+        let _ = validate_preset(value);
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mpk_mini_ctl::mpkmidi::MidiStreamParser;
+
+fuzz_target!(|chunks: Vec<Vec<u8>>| {
+    let mut parser = MidiStreamParser::new();
+    for chunk in chunks {
+        let _ = parser.push(&chunk);
+    }
+    let _ = parser.finish();
+});
@@ -0,0 +1,331 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::DeserializeOwned;
+
+use crate::error::AppError;
+use crate::mpkbank::*;
+use crate::u14::U14BE;
+use crate::validate::validate_fields;
+
+/// Field of a bank descriptor, addressed by `field`, `knobs[N].field` (N = 0..7) or
+/// `pads[P].field` (P = 0..15, or the pad label A1..B8), like the paths of diffs and validation problems.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum FieldPath {
+    Scalar(String),
+    Knob(usize, String),
+    Pad(usize, String),
+}
+
+/// Single `path=value` assignment, e.g. `knobs[3].control=74` or `pads[A5].note=C# 3`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Assignment {
+    path: FieldPath,
+    value: String,
+}
+
+fn parse_index(index: &str, len: usize) -> Option<usize> {
+    index.parse::<usize>().ok().filter(|&n| n < len)
+}
+
+fn parse_pad_index(index: &str) -> Option<usize> {
+    if let Some(n) = parse_index(index, 16) {
+        return Some(n);
+    }
+    let (padbank, n) = index.split_at_checked(1)?;
+    let offset = match padbank {
+        "A" | "a" => 0,
+        "B" | "b" => 8,
+        _ => return None,
+    };
+    match n.parse::<usize>() {
+        Ok(n) if (1..=8).contains(&n) => Some(offset + n - 1),
+        _ => None,
+    }
+}
+
+impl FromStr for FieldPath {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, AppError> {
+        let unknown = || AppError::AssignmentUnknownField(s.to_owned());
+        let Some((array, rest)) = s.split_once('[') else {
+            return Ok(FieldPath::Scalar(s.to_owned()));
+        };
+        let (index, field) = rest.split_once("].").ok_or_else(unknown)?;
+        match array {
+            "knobs" => Ok(FieldPath::Knob(
+                parse_index(index, 8).ok_or_else(unknown)?,
+                field.to_owned(),
+            )),
+            "pads" => Ok(FieldPath::Pad(
+                parse_pad_index(index).ok_or_else(unknown)?,
+                field.to_owned(),
+            )),
+            _ => Err(unknown()),
+        }
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldPath::Scalar(field) => write!(f, "{field}"),
+            FieldPath::Knob(index, field) => write!(f, "knobs[{index}].{field}"),
+            FieldPath::Pad(index, field) => write!(f, "pads[{index}].{field}"),
+        }
+    }
+}
+
+impl FromStr for Assignment {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, AppError> {
+        let (path, value) = s
+            .split_once('=')
+            .ok_or_else(|| AppError::AssignmentSyntax(s.to_owned()))?;
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        Ok(Assignment {
+            path: path.trim().parse()?,
+            value: value.to_owned(),
+        })
+    }
+}
+
+impl Assignment {
+    fn invalid_value(&self, field: &str) -> AppError {
+        AppError::AssignmentValueInvalid(field.to_owned(), self.value.clone())
+    }
+
+    fn parse<T: FromStr>(&self, field: &str) -> Result<T, AppError> {
+        self.value.parse::<T>().map_err(|_| self.invalid_value(field))
+    }
+
+    // Values in yaml syntax, e.g. `!ControlChannel 1`
+    fn parse_yaml<T: DeserializeOwned>(&self, field: &str) -> Result<T, AppError> {
+        serde_yaml::from_str(&self.value).map_err(|_| self.invalid_value(field))
+    }
+
+    // Enum values by name (e.g. `Random`) or by device value (e.g. `5`)
+    fn parse_enum<T: DeserializeOwned>(&self, field: &str, from: fn(u8) -> Result<T, AppError>) -> Result<T, AppError> {
+        match self.value.parse::<u8>() {
+            Ok(value) => from(value),
+            Err(_) => self.parse_yaml(field),
+        }
+    }
+
+    fn parse_note(&self, field: &str, middle_c: MiddleC) -> Result<Note, AppError> {
+        Note::from_str_in(&self.value, middle_c).map_err(|_| self.invalid_value(field))
+    }
+
+    fn apply_knob(&self, knob: Knob, field: &str) -> Result<Knob, AppError> {
+        Ok(match field {
+            "control" => Knob::new(self.parse(field)?, knob.min(), knob.max()),
+            "min" => Knob::new(knob.control(), self.parse(field)?, knob.max()),
+            "max" => Knob::new(knob.control(), knob.min(), self.parse(field)?),
+            _ => return Err(AppError::AssignmentUnknownField(format!("knobs[].{field}"))),
+        })
+    }
+
+    fn apply_pad(&self, pad: Pad, field: &str, middle_c: MiddleC) -> Result<Pad, AppError> {
+        Ok(match field {
+            "note" => Pad::new(
+                self.parse_note(field, middle_c)?,
+                pad.control(),
+                pad.program(),
+                pad.mode(),
+            ),
+            "control" => Pad::new(pad.note(), self.parse(field)?, pad.program(), pad.mode()),
+            "program" => Pad::new(pad.note(), pad.control(), self.parse(field)?, pad.mode()),
+            "mode" => Pad::new(
+                pad.note(),
+                pad.control(),
+                pad.program(),
+                self.parse_enum(field, PadMode::from)?,
+            ),
+            _ => return Err(AppError::AssignmentUnknownField(format!("pads[].{field}"))),
+        })
+    }
+
+    /// Returns `bank_desc` with this assignment applied, with note names in the `middle_c` convention.
+    pub fn apply(&self, bank_desc: MpkBankDescriptor, middle_c: MiddleC) -> Result<MpkBankDescriptor, AppError> {
+        let field = match &self.path {
+            FieldPath::Knob(index, field) => {
+                let knob = self.apply_knob(bank_desc.knobs()[*index], field)?;
+                return bank_desc.with_knob(*index, knob);
+            }
+            FieldPath::Pad(index, field) => {
+                let pad = self.apply_pad(bank_desc.pads()[*index], field, middle_c)?;
+                return bank_desc.with_pad(*index, pad);
+            }
+            FieldPath::Scalar(field) => field.as_str(),
+        };
+
+        Ok(match field {
+            "octave" => bank_desc.with_octave(Octave::new(self.parse(field)?)?),
+            "transpose" => bank_desc.with_transpose(Transpose::new(self.parse(field)?)?),
+            "pad_midi_channel" => bank_desc.with_pad_midi_channel(MidiChannel::new(self.parse(field)?)?),
+            "keybed_channel" => bank_desc.with_keybed_channel(MidiChannel::new(self.parse(field)?)?),
+            "joystick_x" => bank_desc.with_joystick_x(self.parse_yaml(field)?),
+            "joystick_y" => bank_desc.with_joystick_y(self.parse_yaml(field)?),
+            "arpeggiator" => bank_desc.with_arpeggiator(self.parse_enum(field, Toggle::from)?),
+            "arpeggiator_mode" => bank_desc.with_arpeggiator_mode(self.parse_enum(field, ArpeggiatorMode::from)?),
+            "arpeggiator_time_division" => {
+                bank_desc.with_arpeggiator_time_division(self.parse_enum(field, ArpeggiatorTimeDivision::from)?)
+            }
+            "arpeggiator_octave" => bank_desc.with_arpeggiator_octave(ArpeggiatorOctave::new(self.parse(field)?)?),
+            "swing" => bank_desc.with_swing(self.parse_enum(field, Swing::from)?),
+            "latch" => bank_desc.with_latch(self.parse_enum(field, Toggle::from)?),
+            "clock_source" => bank_desc.with_clock_source(self.parse_enum(field, ClockSource::from)?),
+            "tempo_taps" => bank_desc.with_tempo_taps(self.parse(field)?),
+            "tempo" => bank_desc.with_tempo(U14BE::new(self.parse(field)?)?),
+            _ => return Err(AppError::AssignmentUnknownField(field.to_owned())),
+        })
+    }
+}
+
+/// Applies all `assignments` in order, with note names in the `middle_c` convention.
+///
+/// Only the assigned fields are validated: other fields keep the values read from the device, even
+/// if they are out of the documented ranges.
+pub fn apply_assignments(
+    bank_desc: MpkBankDescriptor,
+    assignments: &[Assignment],
+    middle_c: MiddleC,
+) -> Result<MpkBankDescriptor, AppError> {
+    let bank_desc = assignments
+        .iter()
+        .try_fold(bank_desc, |bank_desc, a| a.apply(bank_desc, middle_c))?;
+    let fields: Vec<_> = assignments.iter().map(|a| a.path.to_string()).collect();
+    validate_fields(&bank_desc, &fields)?;
+    Ok(bank_desc)
+}
+
+#[test]
+fn test_apply_assignments() {
+    let assignments: Vec<Assignment> = [
+        "knobs[3].control=74",
+        "pads[A5].note=\"C# 3\"",
+        "pads[B1].mode=Toggle",
+        "arpeggiator_mode=Random",
+        "swing=2",
+        "tempo=128",
+        "octave=-2",
+        "keybed_channel=16",
+        "joystick_y=!SplitControlChannels [1, 2]",
+    ]
+    .iter()
+    .map(|s| s.parse().unwrap())
+    .collect();
+
+    let bank_desc = apply_assignments(MpkBankDescriptor::default(), &assignments, MiddleC::C4).unwrap();
+    assert_eq!(74, bank_desc.knobs()[3].control());
+    assert_eq!(49, bank_desc.pads()[4].note().value());
+    assert_eq!(PadMode::Toggle, bank_desc.pads()[8].mode());
+    assert_eq!(ArpeggiatorMode::Random, bank_desc.arpeggiator_mode());
+    assert_eq!(Swing::_57, bank_desc.swing());
+    assert_eq!(128, bank_desc.tempo().value());
+    assert_eq!(-2, bank_desc.octave().value());
+    assert_eq!(16, bank_desc.keybed_channel().value());
+    assert_eq!(Joystick::SplitControlChannels(1, 2), bank_desc.joystick_y());
+
+    let apply = |s: &str| {
+        s.parse::<Assignment>()
+            .and_then(|a| a.apply(MpkBankDescriptor::default(), MiddleC::C4))
+    };
+    assert!(matches!(
+        apply("knobs[8].control=1"),
+        Err(AppError::AssignmentUnknownField(_))
+    ));
+    assert!(matches!(
+        apply("pads[B9].note=1"),
+        Err(AppError::AssignmentUnknownField(_))
+    ));
+    assert_eq!(1, apply("pads[0].program=1").unwrap().pads()[0].program());
+    assert_eq!(2, apply("pads[15].program=2").unwrap().pads()[15].program());
+    let c3 = "pads[0].note=C 3".parse::<Assignment>().unwrap();
+    assert_eq!(
+        60,
+        c3.apply(MpkBankDescriptor::default(), MiddleC::C3).unwrap().pads()[0]
+            .note()
+            .value()
+    );
+
+    // Paths of diffs address the same field
+    let changed = apply("knobs[0].max=100").unwrap();
+    let diffs = crate::diff::diff_bank_desc(&MpkBankDescriptor::default(), &changed, MiddleC::C4).unwrap();
+    assert_eq!("knobs[0].max", diffs[0].path);
+    let assignment = format!("{}=100", diffs[0].path);
+    assert_eq!(changed, apply(&assignment).unwrap());
+    assert!(matches!(apply("volume=1"), Err(AppError::AssignmentUnknownField(_))));
+    assert!(matches!(
+        apply("arpeggiator_mode=Sideways"),
+        Err(AppError::AssignmentValueInvalid(..))
+    ));
+    assert!(matches!(
+        apply("arpeggiator_mode=9"),
+        Err(AppError::ArpeggiatorModeInvalid(9))
+    ));
+    assert!(matches!(apply("octave"), Err(AppError::AssignmentSyntax(_))));
+    assert!(matches!(
+        apply("octave=5"),
+        Err(AppError::ValueOutOfRange("Octave", 5, -4, 4))
+    ));
+}
+
+#[test]
+fn test_apply_assignments_validation() {
+    let apply = |bank_desc: MpkBankDescriptor, assignments: &[&str]| {
+        let assignments: Vec<Assignment> = assignments.iter().map(|s| s.parse().unwrap()).collect();
+        apply_assignments(bank_desc, &assignments, MiddleC::C4)
+    };
+    assert!(matches!(
+        apply(MpkBankDescriptor::default(), &["knobs[0].min=100", "knobs[0].max=10"]),
+        Err(AppError::ValidationFailed(p)) if p.len() == 1 && p[0].path == "knobs[0]"
+    ));
+    assert!(matches!(
+        apply(MpkBankDescriptor::default(), &["tempo_taps=9"]),
+        Err(AppError::ValidationFailed(p)) if p[0].path == "tempo_taps"
+    ));
+
+    // Out of range values read from the device do not block other assignments
+    let device = MpkBankDescriptor::default()
+        .with_tempo_taps(9)
+        .with_knob(1, Knob::new(1, 100, 10))
+        .unwrap();
+    let bank_desc = apply(device, &["latch=On", "knobs[0].max=64"]).unwrap();
+    assert_eq!(9, bank_desc.tempo_taps());
+    assert!(matches!(
+        apply(bank_desc, &["knobs[1].control=2"]),
+        Err(AppError::ValidationFailed(p)) if p[0].path == "knobs[1]"
+    ));
+}
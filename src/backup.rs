@@ -0,0 +1,183 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+use std::io::Read;
+
+use serde::Deserialize;
+use serde_derive::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use crate::error::AppError;
+use crate::mpkbank::{MiddleC, MpkBankDescriptor, MpkBankDescriptorFile};
+use crate::preset::{Preset, PRESET_MODEL};
+
+/// RAM (0) and banks 1-4.
+pub const ALL_BANKS: [u8; 5] = [0, 1, 2, 3, 4];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BackupMetadata {
+    /// Preset schema version of the bank settings, missing in backups with bare (version 1 or 2) settings
+    #[serde(default)]
+    pub version: Option<u32>,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Note octave convention of the bank settings, missing before preset version 4
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub middle_c: Option<MiddleC>,
+    pub tool_version: String,
+    /// RFC 3339 (UTC)
+    pub timestamp: String,
+    pub port: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BackupBank {
+    /// 0 = RAM
+    pub bank: u8,
+    pub settings: MpkBankDescriptor,
+}
+
+// Bank document as read from file, with settings of any preset version
+#[derive(Serialize, Deserialize)]
+struct BackupBankFile {
+    bank: u8,
+    settings: Value,
+}
+
+impl BackupBankFile {
+    fn migrate(self, metadata: &BackupMetadata) -> Result<BackupBank, AppError> {
+        let preset = match metadata.version {
+            None => self.settings,
+            Some(version) => {
+                let mut preset = Mapping::new();
+                preset.insert(Value::from("version"), Value::from(version));
+                preset.insert(
+                    Value::from("model"),
+                    Value::from(metadata.model.as_deref().unwrap_or(PRESET_MODEL)),
+                );
+                if let Some(middle_c) = metadata.middle_c {
+                    preset.insert(Value::from("middle_c"), serde_yaml::to_value(middle_c)?);
+                }
+                preset.insert(Value::from("settings"), self.settings);
+                Value::Mapping(preset)
+            }
+        };
+        Ok(BackupBank {
+            bank: self.bank,
+            settings: Preset::from_value(preset)?.settings,
+        })
+    }
+}
+
+/// Settings of several banks of one device.
+///
+/// Stored as a multi-document yaml file: the metadata document followed by one document per bank.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backup {
+    pub metadata: BackupMetadata,
+    pub banks: Vec<BackupBank>,
+}
+
+impl Backup {
+    pub fn bank(&self, bank: u8) -> Option<&MpkBankDescriptor> {
+        self.banks.iter().find(|b| b.bank == bank).map(|b| &b.settings)
+    }
+
+    pub fn to_yaml(&self) -> Result<String, AppError> {
+        let mut ret = format!("---\n{}", serde_yaml::to_string(&self.metadata)?);
+        let middle_c = self.metadata.middle_c.unwrap_or_default();
+        for bank in &self.banks {
+            let bank = BackupBankFile {
+                bank: bank.bank,
+                settings: serde_yaml::to_value(MpkBankDescriptorFile::new(&bank.settings, middle_c))?,
+            };
+            ret.push_str(&format!("---\n{}", serde_yaml::to_string(&bank)?));
+        }
+        Ok(ret)
+    }
+
+    pub fn from_yaml<R: Read>(reader: R) -> Result<Self, AppError> {
+        let mut documents = serde_yaml::Deserializer::from_reader(reader);
+        let metadata = match documents.next() {
+            Some(document) => BackupMetadata::deserialize(document)?,
+            None => return Err(AppError::BackupInvalid("missing metadata".to_owned())),
+        };
+        let banks = documents
+            .map(|document| BackupBankFile::deserialize(document)?.migrate(&metadata))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Backup { metadata, banks })
+    }
+}
+
+#[test]
+fn test_backup_yaml() {
+    use crate::mpkbank::Octave;
+    use crate::preset::PRESET_VERSION;
+
+    let backup = Backup {
+        metadata: BackupMetadata {
+            version: Some(PRESET_VERSION),
+            model: Some(PRESET_MODEL.to_owned()),
+            middle_c: Some(MiddleC::C4),
+            tool_version: "0.1.1".to_owned(),
+            timestamp: "2022-01-01T00:00:00Z".to_owned(),
+            port: "MPKmini2 20:0".to_owned(),
+        },
+        banks: vec![
+            BackupBank {
+                bank: 0,
+                settings: MpkBankDescriptor::default(),
+            },
+            BackupBank {
+                bank: 3,
+                settings: MpkBankDescriptor::default().with_octave(Octave::new(3).unwrap()),
+            },
+        ],
+    };
+
+    let yaml = backup.to_yaml().unwrap();
+    let parsed = Backup::from_yaml(yaml.as_bytes()).unwrap();
+    assert_eq!(backup, parsed);
+    assert_eq!(3, parsed.bank(3).unwrap().octave().value());
+    assert!(parsed.bank(1).is_none());
+
+    // Notes are written in the recorded convention
+    let mut c3 = backup.clone();
+    c3.metadata.middle_c = Some(MiddleC::C3);
+    let c3_yaml = c3.to_yaml().unwrap();
+    assert_ne!(yaml.replace("middle_c: C4", "middle_c: C3"), c3_yaml);
+    assert_eq!(c3, Backup::from_yaml(c3_yaml.as_bytes()).unwrap());
+
+    // Version 3 backups have no middle_c, and backups without a version hold bare settings, migrated like
+    // version 1 and 2 presets
+    let v3 = yaml
+        .replace("version: 4\n", "version: 3\n")
+        .replace("middle_c: C4\n", "");
+    assert_eq!(backup.banks, Backup::from_yaml(v3.as_bytes()).unwrap().banks);
+    let legacy = yaml
+        .replace("version: 4\nmodel: MPK Mini mkII\nmiddle_c: C4\n", "")
+        .replace("\n  octave: ", "\n  format_version: 2\n  octave: ");
+    assert_eq!(backup.banks, Backup::from_yaml(legacy.as_bytes()).unwrap().banks);
+}
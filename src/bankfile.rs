@@ -0,0 +1,177 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+//! Bank descriptor files in any supported format: presets (yaml, JSON, TOML), MPK Editor presets
+//! (`.mk2`) and raw sysex files (`.syx`), with the format chosen by file extension unless given.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::error::AppError;
+use crate::mk2;
+use crate::mpkbank::{MiddleC, MpkBankDescriptor};
+use crate::preset::{Preset, PresetFormat};
+use crate::syx;
+
+/// Format of bank descriptor files
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FileFormat {
+    /// Versioned yaml preset
+    Yaml,
+    /// Versioned JSON preset
+    Json,
+    /// Versioned TOML preset
+    Toml,
+    /// MPK Editor preset file
+    Mk2,
+    /// Bank messages as sent by the device
+    Syx,
+    /// Set bank messages, to replay on the device (e.g. `amidi -s`)
+    SyxSet,
+}
+
+impl FileFormat {
+    /// Format by file extension: `.json`, `.toml`, `.mk2`, `.syx`, otherwise yaml.
+    pub fn from_filename(filename: &str) -> Self {
+        let ext = Path::new(filename)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        match ext.to_ascii_lowercase().as_str() {
+            "json" => FileFormat::Json,
+            "toml" => FileFormat::Toml,
+            "mk2" => FileFormat::Mk2,
+            "syx" => FileFormat::Syx,
+            _ => FileFormat::Yaml,
+        }
+    }
+
+    /// Text format of versioned presets, `None` for the device formats.
+    pub fn preset_format(self) -> Option<PresetFormat> {
+        match self {
+            FileFormat::Yaml => Some(PresetFormat::Yaml),
+            FileFormat::Json => Some(PresetFormat::Json),
+            FileFormat::Toml => Some(PresetFormat::Toml),
+            FileFormat::Mk2 | FileFormat::Syx | FileFormat::SyxSet => None,
+        }
+    }
+}
+
+/// Preset format of a file, by extension.
+pub fn preset_file_format(filename: &str) -> Result<PresetFormat, AppError> {
+    FileFormat::from_filename(filename)
+        .preset_format()
+        .ok_or_else(|| AppError::PresetInvalid(format!("{filename} is not a preset file")))
+}
+
+/// Decodes the bank descriptors in `reader`, with the bank number of each message for `.syx` files.
+pub fn read_banks<R: Read>(
+    mut reader: R,
+    format: FileFormat,
+) -> Result<Vec<(Option<u8>, MpkBankDescriptor)>, AppError> {
+    Ok(match format.preset_format() {
+        Some(format) => {
+            let mut s = String::new();
+            reader.read_to_string(&mut s)?;
+            vec![(None, Preset::from_str_as(&s, format)?.settings)]
+        }
+        None if format == FileFormat::Mk2 => vec![(None, mk2::read_mk2(reader)?)],
+        None => syx::read_syx(reader)?
+            .into_iter()
+            .map(|(bank, bank_desc)| (Some(bank), bank_desc))
+            .collect(),
+    })
+}
+
+/// Reads the bank descriptors in a file, in `format` or by extension.
+pub fn read_bank_files(
+    filename: &str,
+    format: Option<FileFormat>,
+) -> Result<Vec<(Option<u8>, MpkBankDescriptor)>, AppError> {
+    let format = format.unwrap_or_else(|| FileFormat::from_filename(filename));
+    read_banks(File::open(filename)?, format)
+}
+
+/// Reads the bank descriptor in a file; for `.syx` files with several banks, the one of `bank`.
+pub fn read_bank_file(
+    filename: &str,
+    format: Option<FileFormat>,
+    bank: Option<u8>,
+) -> Result<MpkBankDescriptor, AppError> {
+    let mut bank_descs = read_bank_files(filename, format)?;
+    if bank_descs.len() == 1 {
+        return Ok(bank_descs.remove(0).1);
+    }
+    match bank_descs.into_iter().find(|(b, _)| bank.is_some() && *b == bank) {
+        Some((_, bank_desc)) => Ok(bank_desc),
+        None => match bank {
+            Some(bank) => Err(AppError::FileBankMissing(filename.to_owned(), bank)),
+            None => Err(AppError::FileSeveralBanks(filename.to_owned())),
+        },
+    }
+}
+
+/// Encodes `bank_desc` (of `bank`, if known) in `format`, with note names in the `middle_c` convention.
+pub fn write_bank(
+    bank_desc: MpkBankDescriptor,
+    bank: Option<u8>,
+    format: FileFormat,
+    middle_c: MiddleC,
+) -> Result<Vec<u8>, AppError> {
+    Ok(match format.preset_format() {
+        Some(format) => Preset::new(bank_desc)
+            .with_middle_c(middle_c)
+            .to_string_as(format)?
+            .into_bytes(),
+        None if format == FileFormat::Mk2 => {
+            let program = bank.filter(|&bank| bank > 0).unwrap_or(mk2::MK2_DEFAULT_PROGRAM);
+            mk2::to_mk2(bank_desc, program)?
+        }
+        None if format == FileFormat::SyxSet => syx::to_syx_set_bank(bank.unwrap_or(0), bank_desc)?,
+        None => syx::to_syx(bank.unwrap_or(0), bank_desc)?,
+    })
+}
+
+#[test]
+fn test_file_formats() {
+    use crate::mpkbank::{Joystick, Octave};
+
+    assert_eq!(FileFormat::Toml, FileFormat::from_filename("bank.TOML"));
+    assert_eq!(FileFormat::Yaml, FileFormat::from_filename("bank"));
+    assert!(preset_file_format("dump.syx").is_err());
+
+    let bank_desc = MpkBankDescriptor::default()
+        .with_octave(Octave::new(-1).unwrap())
+        .with_joystick_y(Joystick::SplitControlChannels(1, 2));
+    for format in FileFormat::value_variants() {
+        let bytes = write_bank(bank_desc.clone(), Some(2), *format, MiddleC::C3).unwrap();
+        let banks = read_banks(bytes.as_slice(), *format).unwrap();
+        assert_eq!(1, banks.len(), "{format:?}");
+        assert_eq!(bank_desc, banks[0].1, "{format:?}");
+    }
+}
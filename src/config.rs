@@ -0,0 +1,85 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+//! User configuration, read from `$XDG_CONFIG_HOME/mpk-mini-ctl/config.toml` (by default
+//! `~/.config/mpk-mini-ctl/config.toml`):
+//!
+//! ```toml
+//! # Octave numbering of note names (C3, C4 or C5)
+//! middle_c = "C3"
+//! ```
+//!
+//! Command line options take precedence over the configuration file.
+
+use std::path::PathBuf;
+
+use serde_derive::Deserialize;
+
+use crate::error::AppError;
+use crate::mpkbank::MiddleC;
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub middle_c: Option<MiddleC>,
+}
+
+impl Config {
+    /// Location of the configuration file, if the home directory is known.
+    pub fn path() -> Option<PathBuf> {
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => home::home_dir()?.join(".config"),
+        };
+        Some(config_dir.join("mpk-mini-ctl").join("config.toml"))
+    }
+
+    pub fn from_toml(s: &str) -> Result<Self, AppError> {
+        Ok(toml::from_str(s)?)
+    }
+
+    /// Reads the configuration file; a missing file is an empty configuration.
+    pub fn load() -> Result<Self, AppError> {
+        let Some(path) = Self::path() else {
+            return Ok(Config::default());
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(s) => Self::from_toml(&s),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[test]
+fn test_config() {
+    assert_eq!(Config::default(), Config::from_toml("").unwrap());
+    assert_eq!(
+        Some(MiddleC::C3),
+        Config::from_toml("middle_c = \"C3\"\n").unwrap().middle_c
+    );
+    assert!(Config::from_toml("middle_c = \"C6\"\n").is_err());
+    assert!(Config::from_toml("midle_c = \"C3\"\n").is_err());
+}
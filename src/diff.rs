@@ -0,0 +1,260 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+use std::fmt;
+
+use serde_derive::Serialize;
+use serde_yaml::Value;
+
+use crate::error::AppError;
+use crate::mpkbank::{MiddleC, MpkBankDescriptor, MpkBankDescriptorFile};
+
+/// A single differing field between two bank descriptors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldDiff {
+    /// Field path, e.g. `knobs[4].control`
+    pub path: String,
+    /// Values as in JSON presets, e.g. `{"ControlChannel": 1}` for enum variants with data
+    pub left: serde_json::Value,
+    pub right: serde_json::Value,
+}
+
+// Value as in JSON presets: yaml tags of enum variants (`!ControlChannel 1`) become single entry maps
+fn json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Sequence(values) => values.iter().map(json_value).collect(),
+        Value::Mapping(mapping) => mapping
+            .iter()
+            .map(|(key, value)| (key_str(key), json_value(value)))
+            .collect(),
+        Value::Tagged(tagged) => [(
+            tagged.tag.to_string().trim_start_matches('!').to_owned(),
+            json_value(&tagged.value),
+        )]
+        .into_iter()
+        .collect(),
+        scalar => serde_json::to_value(scalar).unwrap_or_default(),
+    }
+}
+
+fn key_str(key: &Value) -> String {
+    key.as_str()
+        .map(str::to_owned)
+        .unwrap_or_else(|| value_str(&json_value(key)))
+}
+
+fn join(values: &[serde_json::Value]) -> String {
+    values.iter().map(value_str).collect::<Vec<_>>().join(", ")
+}
+
+// Value on one line, with enum variants like their Rust form (`SplitControlChannels(1, 2)`)
+fn value_str(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(values) => format!("[{}]", join(values)),
+        serde_json::Value::Object(map) => match map.iter().next() {
+            Some((variant, data)) if map.len() == 1 && variant.starts_with(char::is_uppercase) => match data {
+                serde_json::Value::Array(values) => format!("{variant}({})", join(values)),
+                data => format!("{variant}({})", value_str(data)),
+            },
+            _ => {
+                let fields: Vec<_> = map.iter().map(|(k, v)| format!("{k}: {}", value_str(v))).collect();
+                format!("{{{}}}", fields.join(", "))
+            }
+        },
+        other => other.to_string(),
+    }
+}
+
+impl FieldDiff {
+    /// Human readable field name, e.g. `Knob 5 control` or `Pad A3 note`.
+    pub fn label(&self) -> String {
+        let Some((array, rest)) = self.path.split_once('[') else {
+            return self.path.clone();
+        };
+        let Some((index, field)) = rest.split_once(']') else {
+            return self.path.clone();
+        };
+        let Ok(index) = index.parse::<usize>() else {
+            return self.path.clone();
+        };
+        let field = field.trim_start_matches('.').replace('.', " ");
+        let name = match array {
+            "knobs" => format!("Knob {}", index + 1),
+            "pads" => {
+                let padbank = if index < 8 { "A" } else { "B" };
+                format!("Pad {}{}", padbank, index % 8 + 1)
+            }
+            _ => return self.path.clone(),
+        };
+        match field.is_empty() {
+            true => name,
+            false => format!("{name} {field}"),
+        }
+    }
+}
+
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.label(),
+            value_str(&self.left),
+            value_str(&self.right)
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct FieldDiffJson<'a> {
+    path: &'a str,
+    label: String,
+    left: &'a serde_json::Value,
+    right: &'a serde_json::Value,
+}
+
+/// JSON array of `{path, label, left, right}` objects.
+pub fn diffs_to_json(diffs: &[FieldDiff]) -> Result<String, AppError> {
+    let diffs: Vec<FieldDiffJson> = diffs
+        .iter()
+        .map(|d| FieldDiffJson {
+            path: &d.path,
+            label: d.label(),
+            left: &d.left,
+            right: &d.right,
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&diffs)?)
+}
+
+fn child_path(path: &str, key: &Value) -> String {
+    let key = key_str(key);
+    if path.is_empty() {
+        key
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn diff_values(path: String, left: &Value, right: &Value, diffs: &mut Vec<FieldDiff>) {
+    match (left, right) {
+        (Value::Mapping(l), Value::Mapping(r)) => {
+            for (key, lv) in l {
+                diff_values(child_path(&path, key), lv, r.get(key).unwrap_or(&Value::Null), diffs);
+            }
+            for (key, rv) in r {
+                if !l.contains_key(key) {
+                    diff_values(child_path(&path, key), &Value::Null, rv, diffs);
+                }
+            }
+        }
+        (Value::Sequence(l), Value::Sequence(r)) if l.len() == r.len() => {
+            for (i, (lv, rv)) in l.iter().zip(r).enumerate() {
+                diff_values(format!("{path}[{i}]"), lv, rv, diffs);
+            }
+        }
+        _ => {
+            if left != right {
+                diffs.push(FieldDiff {
+                    path,
+                    left: json_value(left),
+                    right: json_value(right),
+                });
+            }
+        }
+    }
+}
+
+/// Fields that differ between `left` and `right`, in serialization order, with note names in the `middle_c`
+/// convention.
+pub fn diff_bank_desc(
+    left: &MpkBankDescriptor,
+    right: &MpkBankDescriptor,
+    middle_c: MiddleC,
+) -> Result<Vec<FieldDiff>, AppError> {
+    let mut diffs = Vec::new();
+    diff_values(
+        String::new(),
+        &serde_yaml::to_value(MpkBankDescriptorFile::new(left, middle_c))?,
+        &serde_yaml::to_value(MpkBankDescriptorFile::new(right, middle_c))?,
+        &mut diffs,
+    );
+    Ok(diffs)
+}
+
+#[test]
+fn test_diff_bank_desc() {
+    use crate::mpkbank::{Joystick, Knob, Octave};
+
+    let left = MpkBankDescriptor::default();
+    assert!(diff_bank_desc(&left, &left, MiddleC::C4).unwrap().is_empty());
+
+    let right = left
+        .clone()
+        .with_octave(Octave::new(2).unwrap())
+        .with_knob(4, Knob::new(74, 0, 127))
+        .unwrap();
+    let diffs = diff_bank_desc(&left, &right, MiddleC::C4).unwrap();
+    let paths: Vec<&str> = diffs.iter().map(|d| d.path.as_str()).collect();
+    assert_eq!(vec!["octave", "knobs[4].control"], paths);
+    assert_eq!("octave: 0 -> 2", diffs[0].to_string());
+    assert_eq!("Knob 5 control: 5 -> 74", diffs[1].to_string());
+
+    // Joystick modes are compared and written as a whole, on one line
+    let right = left
+        .clone()
+        .with_joystick_x(Joystick::ControlChannel(7))
+        .with_joystick_y(Joystick::SplitControlChannels(1, 2));
+    let diffs = diff_bank_desc(&left, &right, MiddleC::C4).unwrap();
+    let lines: Vec<String> = diffs.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        vec![
+            "joystick_x: Pitchbend -> ControlChannel(7)",
+            "joystick_y: ControlChannel(1) -> SplitControlChannels(1, 2)",
+        ],
+        lines
+    );
+    let json: serde_json::Value = serde_json::from_str(&diffs_to_json(&diffs).unwrap()).unwrap();
+    assert_eq!(serde_json::json!({"ControlChannel": 7}), json[0]["right"]);
+    assert_eq!(serde_json::json!({"SplitControlChannels": [1, 2]}), json[1]["right"]);
+    assert_eq!(serde_json::json!("Pitchbend"), json[0]["left"]);
+}
+
+#[test]
+fn test_field_diff_label() {
+    let label = |path: &str| {
+        FieldDiff {
+            path: path.to_owned(),
+            left: serde_json::Value::Null,
+            right: serde_json::Value::Null,
+        }
+        .label()
+    };
+    assert_eq!("Pad A3 note", label("pads[2].note"));
+    assert_eq!("Pad B8 mode", label("pads[15].mode"));
+    assert_eq!("Knob 1", label("knobs[0]"));
+    assert_eq!("tempo.host", label("tempo.host"));
+}
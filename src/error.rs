@@ -26,16 +26,22 @@
 use thiserror::Error;
 
 use crate::mpkbank::MPK_BANK_DESCRIPTOR_LENGTH;
+use crate::preset::{PRESET_MODEL, PRESET_VERSION};
+use crate::validate::{format_problems, ValidationProblem};
 
 /* Runtime Error */
 
 #[derive(Debug, Error)]
 pub enum AppError {
     // Parsing
-    #[error("Cannot parse note/octave string {0} (expected string with exactly one space)")]
+    #[error("Missing or invalid octave in note '{0}' (e.g. `C#3`, `Db -1`)")]
     NoteOctaveParse(String),
-    #[error("cannot parse note {0} (from {1})")]
-    NoteParse(String, String),
+    #[error("Cannot parse note '{0}' (expected e.g. `C#3`, `Db -1`, `60` or a General MIDI drum name)")]
+    NoteParse(String),
+    #[error("Invalid middle C octave '{0}' (expected C3, C4 or C5)")]
+    MiddleCInvalid(String),
+    #[error("Note '{0}' out of range (MIDI note {1}, expected 0..127)")]
+    NoteOutOfRange(String, i64),
     #[error("Unknown value for toggle: {0}")]
     ToggleUnknown(u8),
     #[error("Unknown padmode value: {0}")]
@@ -54,6 +60,10 @@ pub enum AppError {
     BankKnobsUnexpectedLength(usize),
     #[error("trying to parse pads with unexpected length {0} (expected 64)")]
     BankPadsUnexpectedLength(usize),
+    #[error("{0} value {1} out of range ({2}..{3})")]
+    ValueOutOfRange(&'static str, i16, i16, i16),
+    #[error("Invalid {0} value from device: {1}")]
+    DeviceValueInvalid(&'static str, u8),
     #[error("Unexpected length for bank descriptor ({0}, expected {MPK_BANK_DESCRIPTOR_LENGTH})")]
     BankDescriptionUnexpectedLength(usize),
 
@@ -62,8 +72,16 @@ pub enum AppError {
     SysEx(String),
     #[error("received empty message")]
     SysExEmptyMessage,
-    #[error("received message with MSB unset (<127)")]
+    #[error("received message with MSB unset (<0x80)")]
     SysExMsbUnset,
+    #[error("MIDI message {0:#04x} with unexpected length {1} (expected {2})")]
+    MidiMessageLength(u8, usize, usize),
+    #[error("invalid MIDI data {0:#04x}")]
+    MidiDataInvalid(u8),
+    #[error("invalid MIDI channel {0} (expected 0..15)")]
+    MidiChannelInvalid(u8),
+    #[error("MIDI data byte {0:#04x} without status")]
+    MidiDataWithoutStatus(u8),
 
     // U14BE
     #[error("U14BE error: MSB set on U14 type from device {0}/{1}")]
@@ -74,10 +92,56 @@ pub enum AppError {
     // Other
     #[error("Bank value must be between 0 and 4 (0 = RAM), got {0}")]
     BankIndexOutOfBounds(u8),
+    #[error("Knob index must be between 0 and 7, got {0}")]
+    KnobIndexOutOfBounds(usize),
+    #[error("Pad index must be between 0 and 15, got {0}")]
+    PadIndexOutOfBounds(usize),
     #[error("MIDI output port '{0}' not found")]
     MidiOutputPortNotFound(String),
     #[error("MIDI input port '{0}' not found")]
     MidiInputPortNotFound(String),
+    #[error("MPK device {0} not found (see list-devices)")]
+    DeviceNotFound(usize),
+    #[error("Unsupported device (expected an AKAI MPK Mini mkII):\n{0}")]
+    DeviceUnsupported(String),
+    #[error("Device on {0} did not answer the identity request")]
+    DeviceUnidentified(String),
+    #[error("Verification of bank {0} failed: {1} field(s) differ")]
+    VerifyFailed(u8, usize),
+    #[error("Invalid assignment '{0}' (expected field=value)")]
+    AssignmentSyntax(String),
+    #[error("Unknown field '{0}'")]
+    AssignmentUnknownField(String),
+    #[error("Invalid value '{1}' for field '{0}'")]
+    AssignmentValueInvalid(String, String),
+    #[error("Unknown field '{0}' in patch")]
+    PatchUnknownField(String),
+    #[error("Invalid patch: {0}")]
+    PatchInvalid(String),
+    #[error("Invalid backup: {0}")]
+    BackupInvalid(String),
+    #[error("Bank {0} not found in backup")]
+    BackupBankMissing(u8),
+    #[error("Unsupported preset version {0} (latest is {PRESET_VERSION})")]
+    PresetVersionUnsupported(u64),
+    #[error("Unsupported device model '{0}' (expected '{PRESET_MODEL}')")]
+    PresetModelUnsupported(String),
+    #[error("Invalid preset: {0}")]
+    PresetInvalid(String),
+    #[error("Invalid .mk2 preset file: {0}")]
+    Mk2Invalid(String),
+    #[error("Invalid .syx file: {0}")]
+    SyxInvalid(String),
+    #[error("{0} contains several banks")]
+    FileSeveralBanks(String),
+    #[error("{0} contains several banks, but not bank {1}")]
+    FileBankMissing(String, u8),
+    #[error("Invalid hex byte '{0}'")]
+    HexByteInvalid(String),
+    #[error("Invalid bank descriptor: {}", format_problems(.0))]
+    ValidationFailed(Vec<ValidationProblem>),
+    #[error("Invalid port name pattern: {0}")]
+    PortPatternInvalid(#[from] regex::Error),
 
     // midir
     #[error("Midir InitError: {0}")]
@@ -88,6 +152,17 @@ pub enum AppError {
     MidirPortInfoError(#[from] midir::PortInfoError),
     #[error("Midir connect error {0}")]
     MidirConnectError(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    // serde
+    #[error("YAML error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("TOML error: {0}")]
+    TomlDe(#[from] toml::de::Error),
+    #[error("TOML error: {0}")]
+    TomlSer(#[from] toml::ser::Error),
     // mpsc
     #[error("mpsc RecvTimeoutError: {0}")]
     MpscRecvTimeoutError(#[from] std::sync::mpsc::RecvTimeoutError),
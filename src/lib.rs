@@ -0,0 +1,65 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+//! Library for reading and writing AKAI MPK Mini mkII settings.
+//!
+//! [`MpkBankDescriptor`] describes the settings of a single bank (or RAM) and can be
+//! converted from/to the 108 byte device representation and (de)serialized with serde.
+//! Files wrap it in a versioned [`Preset`].
+//! [`MpkMidiMessage`] parses and encodes MIDI messages (e.g. received from the device), and the
+//! [`operations`] module talks to a device through a [`MidiTransport`]: either a connected
+//! device ([`MidirTransport`]) or a simulated one ([`MockMpkMini`]).
+
+pub mod assignment;
+pub mod backup;
+pub mod bankfile;
+pub mod config;
+pub mod diff;
+pub mod error;
+
+#[macro_use]
+mod util;
+
+pub mod mk2;
+pub mod mock;
+pub mod mpkbank;
+pub mod mpkmidi;
+pub mod operations;
+pub mod patch;
+pub mod preset;
+pub mod syx;
+pub mod transport;
+pub mod u14;
+pub mod validate;
+
+pub use crate::backup::Backup;
+pub use crate::error::AppError;
+pub use crate::mock::MockMpkMini;
+pub use crate::mpkbank::MpkBankDescriptor;
+pub use crate::mpkmidi::{sysex_get_bank, sysex_set_bank, MpkMidiMessage};
+pub use crate::preset::Preset;
+pub use crate::transport::{MidiTransport, MidirTransport};
+pub use crate::u14::U14BE;
+pub use crate::util::{is_mpk_port_name, list_devices, list_ports, DevicePorts, MidiPorts, PortSelector};
@@ -23,21 +23,28 @@
  *
  */
 
-mod error;
-
-#[macro_use]
-mod util;
-
-mod mpkbank;
-mod mpkmidi;
-mod operations;
-mod u14;
-
-use crate::mpkbank::MpkBankDescriptor;
+use mpk_mini_ctl::{
+    assignment::Assignment,
+    backup,
+    bankfile::{self, FileFormat},
+    config::Config,
+    diff::{diff_bank_desc, diffs_to_json, FieldDiff},
+    is_mpk_port_name, list_devices, list_ports, mk2,
+    mpkbank::MiddleC,
+    mpkmidi, operations,
+    patch::{apply_patches, BankPatch},
+    preset::{self, Preset, PRESET_VERSION},
+    validate, AppError, Backup, MidiTransport, MidirTransport, MpkBankDescriptor, PortSelector,
+};
 
 use clap::{CommandFactory, Parser, Subcommand};
-use log::debug;
-use std::{fs::File, io::Write};
+use log::{debug, info, warn};
+use std::{
+    convert::Infallible,
+    fs::File,
+    io::{Read, Write},
+    str::FromStr,
+};
 
 /// AKAI MPK Mini mkII Control Tool
 #[derive(Parser, Debug)]
@@ -52,6 +59,27 @@ struct Args {
     #[arg(long)]
     debug: bool,
 
+    /// MPK device index (see list-devices) [default: first MPKmini2 port]
+    #[arg(long, global = true, conflicts_with_all = ["in_port", "out_port"])]
+    device: Option<usize>,
+
+    /// MIDI input port: index (see list-ports), name or regex [default: first MPKmini2 port]
+    #[arg(long, global = true)]
+    in_port: Option<PortSelector>,
+
+    /// MIDI output port: index (see list-ports), name or regex [default: first MPKmini2 port]
+    #[arg(long, global = true)]
+    out_port: Option<PortSelector>,
+
+    /// File format: output of dump-bank-settings, dump-ram-settings and read-file, input of load-bank and
+    /// load-ram [default: yaml output, input by file extension]
+    #[arg(long, global = true, value_enum)]
+    format: Option<FileFormat>,
+
+    /// Octave of middle C (MIDI note 60) in note names: C3, C4 or C5 [default: config file middle_c, or C4]
+    #[arg(long, global = true)]
+    middle_c: Option<MiddleC>,
+
     #[command(subcommand)]
     command: Command,
 }
@@ -64,26 +92,192 @@ enum Command {
     /// Passthrough (while snooping) MIDI messages
     Passthrough,
 
+    /// Decode MIDI messages from a raw MIDI file (e.g. recorded with `amidi -r`) or stdin
+    Decode {
+        /// [default: stdin]
+        filename: Option<String>,
+
+        /// Read hex bytes (e.g. `amidi -d` output) instead of raw bytes
+        #[arg(long)]
+        hex: bool,
+    },
+
     /// Show bank settings
     ShowBank { bank: u8 },
 
     /// Show current active settings (RAM)
     ShowRAM,
 
-    /// Read yaml bank descriptor from file and display it
+    /// Read bank descriptor(s) from file and display them (or convert them with --format)
     ReadFile { filename: String },
 
-    /// Dump bank settings as yaml
+    /// Dump bank settings as yaml (or --format)
     DumpBankSettings { bank: u8 },
 
-    /// Dump current active settings (RAM) as yaml
+    /// Dump current active settings (RAM) as yaml (or --format)
     DumpRAMSettings,
 
-    /// Read yaml bank descriptor from file and apply it on a bank
-    LoadBank { filename: String, bank: u8 },
+    /// Read bank descriptor from file and apply it on a bank
+    LoadBank {
+        filename: String,
+        bank: u8,
+
+        /// Read the bank back and fail if it differs from the file
+        #[arg(long)]
+        verify: bool,
+    },
+
+    /// Read bank descriptor from file and apply it to active settings (RAM)
+    LoadRAM {
+        filename: String,
+
+        /// Read the settings back and fail if they differ from the file
+        #[arg(long)]
+        verify: bool,
+    },
+
+    /// Back up active settings (RAM) and banks 1-4 to a multi-document yaml file
+    Backup {
+        filename: String,
+
+        /// Comma separated banks to back up (0 = RAM)
+        #[arg(long, value_delimiter = ',', default_values_t = backup::ALL_BANKS)]
+        banks: Vec<u8>,
+    },
+
+    /// Restore active settings (RAM) and banks 1-4 from a backup file
+    Restore {
+        filename: String,
+
+        /// Comma separated banks to restore (0 = RAM)
+        #[arg(long, value_delimiter = ',', default_values_t = backup::ALL_BANKS)]
+        banks: Vec<u8>,
+    },
+
+    /// List MIDI input and output ports
+    ListPorts,
+
+    /// List connected MPK devices and their active settings
+    ListDevices,
+
+    /// Show the model and firmware reported by the device
+    Identify,
+
+    /// Copy bank settings to another bank
+    CopyBank {
+        /// Source: `ram` or bank number (1-4)
+        #[arg(value_parser = parse_bank)]
+        src: u8,
+
+        /// Destination: `ram` or bank number (1-4)
+        #[arg(value_parser = parse_bank)]
+        dst: u8,
+
+        /// Only show the changes to the destination
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Swap the settings of two banks
+    SwapBanks {
+        /// `ram` or bank number (1-4)
+        #[arg(value_parser = parse_bank)]
+        a: u8,
+
+        /// `ram` or bank number (1-4)
+        #[arg(value_parser = parse_bank)]
+        b: u8,
 
-    /// Read yaml bank descriptor from file and apply it to active settings (RAM)
-    LoadRAM { filename: String },
+        /// Only show the changes to both banks
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Set individual fields of a bank, e.g. `knobs[3].control=74 pads[A5].note="C# 3" arpeggiator_mode=Random`
+    Set {
+        /// `ram` or bank number (1-4)
+        #[arg(value_parser = parse_bank)]
+        bank: u8,
+
+        /// field=value, with knobs[0..7] and pads[0..15] (or pad labels A1..B8)
+        #[arg(required = true)]
+        assignments: Vec<Assignment>,
+    },
+
+    /// Merge partial yaml descriptors (patches) onto a base and print the result as yaml
+    Merge {
+        /// Base: `ram`, bank number (1-4) or yaml file
+        base: BankSource,
+
+        /// Patch files, applied in order
+        #[arg(required = true)]
+        patches: Vec<String>,
+    },
+
+    /// Merge partial yaml descriptors (patches) onto the current settings of a bank and apply them
+    LoadPatch {
+        /// `ram` or bank number (1-4)
+        #[arg(value_parser = parse_bank)]
+        bank: u8,
+
+        /// Patch files, applied in order
+        #[arg(required = true)]
+        patches: Vec<String>,
+
+        /// Merge onto this base (`ram`, bank number or yaml file) instead of the bank's current settings
+        #[arg(long)]
+        base: Option<BankSource>,
+
+        /// Read the bank back and fail if it differs
+        #[arg(long)]
+        verify: bool,
+    },
+
+    /// Show differing fields between two bank descriptors
+    Diff {
+        /// `ram`, bank number (1-4) or yaml file
+        left: BankSource,
+
+        /// `ram`, bank number (1-4) or yaml file
+        right: BankSource,
+
+        /// Output differences as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Convert an MPK Editor preset file (.mk2) to a yaml preset
+    Import {
+        filename: String,
+
+        /// Output yaml file [default: standard output]
+        output: Option<String>,
+    },
+
+    /// Convert a yaml preset to an MPK Editor preset file (.mk2)
+    Export {
+        filename: String,
+        output: String,
+
+        /// Program number stored in the preset file (1-4)
+        #[arg(long, default_value_t = mk2::MK2_DEFAULT_PROGRAM, value_parser = clap::value_parser!(u8).range(1..=4))]
+        program: u8,
+    },
+
+    /// Check preset files for values the device does not accept; fails if any file is invalid
+    Validate {
+        #[arg(required = true)]
+        filenames: Vec<String>,
+    },
+
+    /// Print the JSON Schema of preset files, e.g. for editor completion and validation
+    Schema,
+
+    /// Rewrite preset files in place to the latest schema version
+    Migrate {
+        #[arg(required = true)]
+        filenames: Vec<String>,
+    },
 
     /// Install local bash auto-completion
     Autocompletion {
@@ -95,16 +289,320 @@ enum Command {
     },
 }
 
-fn read_yaml(filename: &str) -> anyhow::Result<()> {
-    let bank_desc: MpkBankDescriptor = serde_yaml::from_reader(File::open(filename)?)?;
-    println!("{bank_desc}");
-    debug!("{:?}", bank_desc.into_bytes());
+/// Bank descriptor from the device (`ram` or bank number) or from a yaml file
+#[derive(Clone, Debug)]
+enum BankSource {
+    Bank(u8),
+    File(String),
+}
+
+impl FromStr for BankSource {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Infallible> {
+        Ok(match s {
+            "ram" | "RAM" => BankSource::Bank(0),
+            _ => match s.parse::<u8>() {
+                Ok(bank) if bank <= 4 => BankSource::Bank(bank),
+                _ => BankSource::File(s.to_owned()),
+            },
+        })
+    }
+}
+
+fn parse_bank(s: &str) -> Result<u8, String> {
+    match BankSource::from_str(s) {
+        Ok(BankSource::Bank(bank)) => Ok(bank),
+        _ => Err(format!("expected `ram` or bank number 1-4, got `{s}`")),
+    }
+}
+
+fn print_bank_diffs(bank: u8, diffs: &[FieldDiff]) {
+    let name = match bank {
+        0 => "RAM".to_owned(),
+        _ => format!("Bank {bank}"),
+    };
+    if diffs.is_empty() {
+        println!("{name}: no changes");
+    } else {
+        println!("{name}:");
+        for d in diffs {
+            println!("  {d}");
+        }
+    }
+}
+
+// Connects on first use, so that comparing files does not require a device
+fn read_source(
+    source: &BankSource,
+    transport: &mut Option<MidirTransport>,
+    connect: &dyn Fn() -> anyhow::Result<MidirTransport>,
+) -> anyhow::Result<MpkBankDescriptor> {
+    match source {
+        BankSource::File(filename) => Ok(bankfile::read_bank_file(filename, None, None)?),
+        BankSource::Bank(bank) => {
+            if transport.is_none() {
+                *transport = Some(connect()?);
+            }
+            Ok(operations::get_bank_desc(transport.as_mut().unwrap(), *bank)?)
+        }
+    }
+}
+
+fn diff(
+    left: &BankSource,
+    right: &BankSource,
+    json: bool,
+    middle_c: MiddleC,
+    connect: &dyn Fn() -> anyhow::Result<MidirTransport>,
+) -> anyhow::Result<()> {
+    let mut transport = None;
+    let left = read_source(left, &mut transport, connect)?;
+    let right = read_source(right, &mut transport, connect)?;
+    let diffs = diff_bank_desc(&left, &right, middle_c)?;
+
+    if json {
+        println!("{}", diffs_to_json(&diffs)?);
+    } else if diffs.is_empty() {
+        println!("No differences");
+    } else {
+        for d in &diffs {
+            println!("{d}");
+        }
+    }
+    Ok(())
+}
+
+// Writes `bank_desc` (of `bank`, if known) to standard output
+fn write_bank(
+    bank_desc: MpkBankDescriptor,
+    bank: Option<u8>,
+    format: FileFormat,
+    middle_c: MiddleC,
+) -> anyhow::Result<()> {
+    std::io::stdout().write_all(&bankfile::write_bank(bank_desc, bank, format, middle_c)?)?;
+    Ok(())
+}
+
+// Displays the file, or converts it to `format`
+fn read_file(filename: &str, format: Option<FileFormat>, middle_c: MiddleC) -> anyhow::Result<()> {
+    for (bank, bank_desc) in bankfile::read_bank_files(filename, None)? {
+        match format {
+            Some(format) => write_bank(bank_desc, bank, format, middle_c)?,
+            None => {
+                if let Some(bank) = bank {
+                    println!("Bank {bank}:");
+                }
+                println!("{}", bank_desc.display_in(middle_c));
+                debug!("{:?}", bank_desc.into_bytes()?);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn dump_bank<T: MidiTransport>(
+    transport: &mut T,
+    bank: u8,
+    format: Option<FileFormat>,
+    middle_c: MiddleC,
+) -> anyhow::Result<()> {
+    match format.unwrap_or(FileFormat::Yaml) {
+        // As received, rather than re-encoded
+        FileFormat::Syx => operations::dump_bank_syx(transport, bank, false)?,
+        FileFormat::SyxSet => operations::dump_bank_syx(transport, bank, true)?,
+        format => write_bank(
+            operations::get_bank_desc(transport, bank)?,
+            Some(bank),
+            format,
+            middle_c,
+        )?,
+    }
+    Ok(())
+}
+
+// Bare patches use `middle_c`; presets use their own convention
+fn read_patches(filenames: &[String], middle_c: MiddleC) -> anyhow::Result<Vec<BankPatch>> {
+    filenames
+        .iter()
+        .map(|filename| Ok(BankPatch::from_reader(File::open(filename)?, middle_c)?))
+        .collect()
+}
+
+fn merge(
+    base: &BankSource,
+    patches: &[String],
+    middle_c: MiddleC,
+    connect: &dyn Fn() -> anyhow::Result<MidirTransport>,
+) -> anyhow::Result<()> {
+    let base = read_source(base, &mut None, connect)?;
+    let merged = apply_patches(base, &read_patches(patches, middle_c)?)?;
+    print!("{}", Preset::new(merged).with_middle_c(middle_c).to_yaml()?);
+    Ok(())
+}
+
+fn load_patch(
+    bank: u8,
+    patches: &[String],
+    base: Option<&BankSource>,
+    verify: bool,
+    middle_c: MiddleC,
+    connect: &dyn Fn() -> anyhow::Result<MidirTransport>,
+) -> anyhow::Result<()> {
+    let patches = read_patches(patches, middle_c)?;
+    let mut transport = Some(connect()?);
+    let current = read_source(&BankSource::Bank(bank), &mut transport, connect)?;
+    let base = match base {
+        Some(base) => read_source(base, &mut transport, connect)?,
+        None => current.clone(),
+    };
+    let merged = apply_patches(base, &patches)?;
+    print_bank_diffs(bank, &diff_bank_desc(&current, &merged, middle_c)?);
+
+    let transport = transport.as_mut().unwrap();
+    match verify {
+        false => operations::set_bank_from_desc(transport, bank, merged)?,
+        true => operations::set_bank_verified(transport, bank, merged, middle_c)?,
+    }
+    Ok(())
+}
+
+// Reads and validates the file before connecting, so that nothing is sent for invalid files
+fn load_file(
+    filename: &str,
+    format: Option<FileFormat>,
+    bank: u8,
+    verify: bool,
+    middle_c: MiddleC,
+    connect: &dyn Fn() -> anyhow::Result<MidirTransport>,
+) -> anyhow::Result<()> {
+    let bank_desc = bankfile::read_bank_file(filename, format, Some(bank))?;
+    bank_desc.validate()?;
+    let transport = &mut connect()?;
+    match verify {
+        false => operations::set_bank_from_desc(transport, bank, bank_desc)?,
+        true => operations::set_bank_verified(transport, bank, bank_desc, middle_c)?,
+    }
     Ok(())
 }
 
-fn load_yaml(filename: &str, bank: u8) -> anyhow::Result<()> {
-    let bank_desc: MpkBankDescriptor = serde_yaml::from_reader(File::open(filename)?)?;
-    operations::set_bank_from_desc(bank, bank_desc)?;
+fn import_mk2(filename: &str, output: Option<&str>, middle_c: MiddleC) -> anyhow::Result<()> {
+    let yaml = Preset::new(bankfile::read_bank_file(filename, Some(FileFormat::Mk2), None)?)
+        .with_middle_c(middle_c)
+        .to_yaml()?;
+    match output {
+        Some(output) => std::fs::write(output, yaml)?,
+        None => print!("{yaml}"),
+    }
+    Ok(())
+}
+
+fn export_mk2(filename: &str, output: &str, program: u8) -> anyhow::Result<()> {
+    let bank_desc = bankfile::read_bank_file(filename, None, None)?;
+    std::fs::write(output, mk2::to_mk2(bank_desc, program)?)?;
+    Ok(())
+}
+
+fn decode_file(filename: Option<&str>, hex: bool) -> anyhow::Result<()> {
+    let reader: Box<dyn Read> = match filename {
+        Some(filename) => Box::new(File::open(filename)?),
+        None => Box::new(std::io::stdin()),
+    };
+    mpkmidi::decode_stream(reader, hex, |result| match result {
+        Ok(msg) => println!("{msg:?}"),
+        Err(e) => warn!("Unparsed: {e}"),
+    })?;
+    Ok(())
+}
+
+fn validate_files(filenames: &[String]) -> anyhow::Result<()> {
+    let mut invalid = 0;
+    for filename in filenames {
+        match validate::validate_file(filename) {
+            Ok(()) => println!("{filename}: ok"),
+            Err(AppError::ValidationFailed(problems)) => {
+                invalid += 1;
+                for problem in problems {
+                    println!("{filename}: {problem}");
+                }
+            }
+            Err(e) => {
+                invalid += 1;
+                println!("{filename}: {e}");
+            }
+        }
+    }
+    if invalid > 0 {
+        anyhow::bail!("{invalid} of {} files invalid", filenames.len());
+    }
+    Ok(())
+}
+
+fn migrate_files(filenames: &[String]) -> anyhow::Result<()> {
+    for filename in filenames {
+        match preset::migrate_file(filename)? {
+            Some(version) => println!("{filename}: migrated from version {version} to {PRESET_VERSION}"),
+            None => println!("{filename}: already at version {PRESET_VERSION}"),
+        }
+    }
+    Ok(())
+}
+
+fn backup_to_file<T: MidiTransport>(
+    transport: &mut T,
+    filename: &str,
+    banks: &[u8],
+    middle_c: MiddleC,
+) -> anyhow::Result<()> {
+    let backup = operations::backup(transport, banks, middle_c)?;
+    File::create(filename)?.write_all(backup.to_yaml()?.as_bytes())?;
+    Ok(())
+}
+
+fn restore_from_file<T: MidiTransport>(transport: &mut T, filename: &str, banks: &[u8]) -> anyhow::Result<()> {
+    let backup = Backup::from_yaml(File::open(filename)?)?;
+    info!(
+        "Backup of {} taken at {} (version {})",
+        backup.metadata.port, backup.metadata.timestamp, backup.metadata.tool_version
+    );
+    operations::restore(transport, &backup, banks)?;
+    Ok(())
+}
+
+fn print_ports() -> anyhow::Result<()> {
+    let ports = list_ports()?;
+    for (heading, names) in [("Input ports:", &ports.inputs), ("Output ports:", &ports.outputs)] {
+        println!("{heading}");
+        for (index, name) in names.iter().enumerate() {
+            let marker = if is_mpk_port_name(name) { " (MPK)" } else { "" };
+            println!("  {index}: {name}{marker}");
+        }
+    }
+    Ok(())
+}
+
+fn print_devices() -> anyhow::Result<()> {
+    let devices = list_devices()?;
+    if devices.is_empty() {
+        println!("No MPK devices found");
+    }
+    for (index, device) in devices.iter().enumerate() {
+        println!(
+            "{index}: in: {} (#{}), out: {} (#{})",
+            device.input_name, device.input_index, device.output_name, device.output_index
+        );
+        let (in_port, out_port) = device.selectors();
+        match MidirTransport::connect(&in_port, &out_port).and_then(|mut t| operations::get_bank_desc(&mut t, 0)) {
+            Ok(ram) => println!(
+                "   RAM: PAD Channel: {}, Keybed Channel: {}, Octave: {}, Tempo: {}",
+                ram.pad_midi_channel(),
+                ram.keybed_channel(),
+                ram.octave(),
+                ram.tempo()
+            ),
+            Err(e) => println!("   cannot read RAM: {e}"),
+        }
+    }
     Ok(())
 }
 
@@ -144,16 +642,71 @@ fn main() -> anyhow::Result<()> {
         simplelog::ColorChoice::Auto,
     )])?;
 
+    let config = Config::load()?;
+    let middle_c = args.middle_c.or(config.middle_c).unwrap_or_default();
+
+    let connect = || -> anyhow::Result<MidirTransport> {
+        let (in_port, out_port) = match args.device {
+            Some(device) => list_devices()?
+                .get(device)
+                .ok_or(AppError::DeviceNotFound(device))?
+                .selectors(),
+            None => (
+                args.in_port.clone().unwrap_or_default(),
+                args.out_port.clone().unwrap_or_default(),
+            ),
+        };
+        Ok(MidirTransport::connect(&in_port, &out_port)?)
+    };
+
     match args.command {
-        Command::Snoop => operations::snoop()?,
-        Command::ShowBank { bank } => operations::show_bank(bank)?,
-        Command::ShowRAM => operations::show_bank(0)?,
-        Command::Passthrough => operations::passthrough()?,
-        Command::ReadFile { filename } => read_yaml(&filename)?,
-        Command::DumpBankSettings { bank } => operations::dump_bank_yaml(bank)?,
-        Command::DumpRAMSettings => operations::dump_bank_yaml(0)?,
-        Command::LoadBank { filename, bank } => load_yaml(&filename, bank)?,
-        Command::LoadRAM { filename } => load_yaml(&filename, 0)?,
+        Command::Snoop => operations::snoop(&mut connect()?)?,
+        Command::ShowBank { bank } => operations::show_bank(&mut connect()?, bank, middle_c)?,
+        Command::ShowRAM => operations::show_bank(&mut connect()?, 0, middle_c)?,
+        Command::Passthrough => operations::passthrough(&mut connect()?)?,
+        Command::Decode { filename, hex } => decode_file(filename.as_deref(), hex)?,
+        Command::ReadFile { filename } => read_file(&filename, args.format, middle_c)?,
+        Command::DumpBankSettings { bank } => dump_bank(&mut connect()?, bank, args.format, middle_c)?,
+        Command::DumpRAMSettings => dump_bank(&mut connect()?, 0, args.format, middle_c)?,
+        Command::LoadBank { filename, bank, verify } => {
+            load_file(&filename, args.format, bank, verify, middle_c, &connect)?
+        }
+        Command::LoadRAM { filename, verify } => load_file(&filename, args.format, 0, verify, middle_c, &connect)?,
+        Command::ListPorts => print_ports()?,
+        Command::ListDevices => print_devices()?,
+        Command::Identify => operations::show_identity(&mut connect()?)?,
+        Command::Backup { filename, banks } => backup_to_file(&mut connect()?, &filename, &banks, middle_c)?,
+        Command::Restore { filename, banks } => restore_from_file(&mut connect()?, &filename, &banks)?,
+        Command::CopyBank { src, dst, dry_run } => print_bank_diffs(
+            dst,
+            &operations::copy_bank(&mut connect()?, src, dst, dry_run, middle_c)?,
+        ),
+        Command::SwapBanks { a, b, dry_run } => {
+            let (a_diffs, b_diffs) = operations::swap_banks(&mut connect()?, a, b, dry_run, middle_c)?;
+            print_bank_diffs(a, &a_diffs);
+            print_bank_diffs(b, &b_diffs);
+        }
+        Command::Set { bank, assignments } => print_bank_diffs(
+            bank,
+            &operations::set_fields(&mut connect()?, bank, &assignments, middle_c)?,
+        ),
+        Command::Merge { base, patches } => merge(&base, &patches, middle_c, &connect)?,
+        Command::LoadPatch {
+            bank,
+            patches,
+            base,
+            verify,
+        } => load_patch(bank, &patches, base.as_ref(), verify, middle_c, &connect)?,
+        Command::Diff { left, right, json } => diff(&left, &right, json, middle_c, &connect)?,
+        Command::Import { filename, output } => import_mk2(&filename, output.as_deref(), middle_c)?,
+        Command::Export {
+            filename,
+            output,
+            program,
+        } => export_mk2(&filename, &output, program)?,
+        Command::Validate { filenames } => validate_files(&filenames)?,
+        Command::Schema => println!("{}", serde_json::to_string_pretty(&Preset::json_schema())?),
+        Command::Migrate { filenames } => migrate_files(&filenames)?,
         Command::Autocompletion { shell, install } => autocompletion(shell, install)?,
     };
 
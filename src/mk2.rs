@@ -0,0 +1,101 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+//! AKAI MPK Mini mkII Editor (Vyzex) preset files (`.mk2`).
+//!
+//! A preset file holds the sysex message writing a program to the device: the set bank header,
+//! the program number and the 108 byte bank descriptor (117 bytes in total). Files holding the
+//! device's bank reply instead are accepted as well.
+
+use std::io::Read;
+
+use crate::error::AppError;
+use crate::mpkbank::{MpkBankDescriptor, MPK_BANK_DESCRIPTOR_LENGTH};
+use crate::mpkmidi::{sysex_set_bank, MIDI_SYSEX, MIDI_SYSEX_END, SYSEX_AKAI, SYSEX_MPK_BANK, SYSEX_MPK_SET_BANK};
+
+// Sysex start, manufacturer, header and program number
+const MK2_PAYLOAD_OFFSET: usize = 2 + SYSEX_MPK_SET_BANK.len() + 1;
+
+/// Length of a `.mk2` preset file.
+pub const MK2_FILE_LENGTH: usize = MK2_PAYLOAD_OFFSET + MPK_BANK_DESCRIPTOR_LENGTH + 1;
+
+/// Program number written to exported files.
+pub const MK2_DEFAULT_PROGRAM: u8 = 1;
+
+/// Decodes the contents of a `.mk2` preset file.
+pub fn from_mk2(bytes: &[u8]) -> Result<MpkBankDescriptor, AppError> {
+    if bytes.len() != MK2_FILE_LENGTH {
+        return Err(AppError::Mk2Invalid(format!(
+            "unexpected length {} (expected {MK2_FILE_LENGTH})",
+            bytes.len()
+        )));
+    }
+    if bytes[0] != MIDI_SYSEX || bytes[1] != SYSEX_AKAI || bytes[MK2_FILE_LENGTH - 1] != MIDI_SYSEX_END {
+        return Err(AppError::Mk2Invalid("not an AKAI sysex message".to_owned()));
+    }
+    let header = &bytes[2..2 + SYSEX_MPK_SET_BANK.len()];
+    if header != SYSEX_MPK_SET_BANK && header != SYSEX_MPK_BANK {
+        return Err(AppError::Mk2Invalid(format!("unknown header {header:02x?}")));
+    }
+    MpkBankDescriptor::from(&bytes[MK2_PAYLOAD_OFFSET..MK2_FILE_LENGTH - 1])
+}
+
+pub fn read_mk2<R: Read>(mut reader: R) -> Result<MpkBankDescriptor, AppError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    from_mk2(&bytes)
+}
+
+/// Encodes `bank_desc` as a `.mk2` preset file for `program` (1-4).
+pub fn to_mk2(bank_desc: MpkBankDescriptor, program: u8) -> Result<Vec<u8>, AppError> {
+    sysex_set_bank(program, bank_desc)
+}
+
+#[test]
+fn test_mk2() {
+    use crate::mpkbank::{ArpeggiatorMode, Note, Octave, Pad, PadMode};
+
+    let pads = std::array::from_fn(|i| Pad::new(Note::new(48 + i as u8), 20 + i as u8, 40, PadMode::Toggle));
+    let bank_desc = MpkBankDescriptor::default()
+        .with_octave(Octave::new(2).unwrap())
+        .with_arpeggiator_mode(ArpeggiatorMode::Exclusive)
+        .with_pads(pads);
+    let bytes = to_mk2(bank_desc.clone(), MK2_DEFAULT_PROGRAM).unwrap();
+    assert_eq!(MK2_FILE_LENGTH, bytes.len());
+    assert_eq!(
+        [0xf0, 0x47, 0x00, 0x26, 0x64, 0x00, 0x6d, 0x01],
+        bytes[..MK2_PAYLOAD_OFFSET]
+    );
+    assert_eq!(bank_desc, from_mk2(&bytes).unwrap());
+
+    let mut reply = bytes.clone();
+    reply[4] = 0x67;
+    assert_eq!(bank_desc, read_mk2(reply.as_slice()).unwrap());
+
+    assert!(matches!(from_mk2(&bytes[..100]), Err(AppError::Mk2Invalid(_))));
+    let mut other = bytes;
+    other[1] = 0x41;
+    assert!(matches!(from_mk2(&other), Err(AppError::Mk2Invalid(_))));
+}
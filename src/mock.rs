@@ -0,0 +1,171 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+use std::collections::VecDeque;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+use log::warn;
+
+use crate::error::AppError;
+use crate::mpkbank::{MpkBankDescriptor, MPK_BANK_DESCRIPTOR_LENGTH};
+use crate::mpkmidi::*;
+use crate::transport::MidiTransport;
+
+const MOCK_BANKS: usize = 5;
+
+/// Simulated MPK Mini mkII holding RAM (bank 0) and banks 1-4.
+///
+/// Answers identity requests and bank requests (`sysex_get_bank`) and stores bank writes
+/// (`sysex_set_bank`). Every sent message is recorded, and arbitrary incoming messages can be queued with [`MockMpkMini::push_incoming`].
+pub struct MockMpkMini {
+    banks: [Vec<u8>; MOCK_BANKS],
+    incoming: VecDeque<Vec<u8>>,
+    sent: Vec<Vec<u8>>,
+    connected: bool,
+    identity: Option<DeviceIdentity>,
+}
+
+impl Default for MockMpkMini {
+    fn default() -> Self {
+        let bank_bytes = MpkBankDescriptor::default().into_bytes().unwrap();
+        MockMpkMini {
+            banks: std::array::from_fn(|_| bank_bytes.clone()),
+            incoming: VecDeque::new(),
+            sent: Vec::new(),
+            connected: true,
+            identity: Some(DeviceIdentity {
+                device_id: 0,
+                manufacturer: SysExId::Manufacturer(SYSEX_AKAI),
+                family: MPK_MINI_MK2_FAMILY,
+                model: 0,
+                firmware: [0; 4],
+            }),
+        }
+    }
+}
+
+impl MockMpkMini {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the identity reported to identity requests (`None` = no reply).
+    pub fn with_identity(mut self, identity: Option<DeviceIdentity>) -> Self {
+        self.identity = identity;
+        self
+    }
+
+    /// Raw bank descriptor bytes of `bank` (0 = RAM).
+    pub fn bank_bytes(&self, bank: u8) -> Result<&[u8], AppError> {
+        self.banks
+            .get(bank as usize)
+            .map(Vec::as_slice)
+            .ok_or(AppError::BankIndexOutOfBounds(bank))
+    }
+
+    pub fn bank(&self, bank: u8) -> Result<MpkBankDescriptor, AppError> {
+        MpkBankDescriptor::from(self.bank_bytes(bank)?)
+    }
+
+    pub fn set_bank(&mut self, bank: u8, bank_desc: MpkBankDescriptor) -> Result<(), AppError> {
+        let slot = self
+            .banks
+            .get_mut(bank as usize)
+            .ok_or(AppError::BankIndexOutOfBounds(bank))?;
+        *slot = bank_desc.into_bytes()?;
+        Ok(())
+    }
+
+    /// Queues a message as if it was sent by the device (e.g., a pad hit).
+    pub fn push_incoming(&mut self, bytes: &[u8]) {
+        self.incoming.push_back(Vec::from(bytes));
+    }
+
+    /// All messages sent to the device so far.
+    pub fn sent(&self) -> &[Vec<u8>] {
+        &self.sent
+    }
+
+    /// Simulates unplugging: once the incoming queue is drained, receiving fails instead of timing out.
+    pub fn disconnect(&mut self) {
+        self.connected = false;
+    }
+
+    fn handle_sysex(&mut self, bytes: &[u8]) {
+        if bytes == sysex_identity_request() {
+            if let Some(identity) = self.identity {
+                self.incoming
+                    .push_back(MpkMidiMessage::IdentityReply(identity).to_bytes().unwrap());
+            }
+            return;
+        }
+        if bytes.len() < 3 || bytes[1] != SYSEX_AKAI || bytes.last() != Some(&MIDI_SYSEX_END) {
+            return;
+        }
+
+        let payload = &bytes[2..bytes.len() - 1];
+        if payload.len() == SYSEX_MPK_GET_BANK.len() + 1 && payload.starts_with(&SYSEX_MPK_GET_BANK) {
+            let bank = payload[SYSEX_MPK_GET_BANK.len()];
+            match self.banks.get(bank as usize) {
+                Some(bank_bytes) => {
+                    let reply = sysex_bank(bank, bank_bytes);
+                    self.incoming.push_back(reply);
+                }
+                None => warn!("Mock: get bank request for invalid bank {} ignored", bank),
+            }
+        } else if payload.len() == SYSEX_MPK_SET_BANK.len() + 1 + MPK_BANK_DESCRIPTOR_LENGTH
+            && payload.starts_with(&SYSEX_MPK_SET_BANK)
+        {
+            let bank = payload[SYSEX_MPK_SET_BANK.len()];
+            match self.banks.get_mut(bank as usize) {
+                Some(slot) => *slot = Vec::from(&payload[SYSEX_MPK_SET_BANK.len() + 1..]),
+                None => warn!("Mock: set bank request for invalid bank {} ignored", bank),
+            }
+        }
+    }
+}
+
+impl MidiTransport for MockMpkMini {
+    fn send(&mut self, bytes: &[u8]) -> Result<(), AppError> {
+        self.sent.push(Vec::from(bytes));
+        if bytes.first() == Some(&MIDI_SYSEX) {
+            self.handle_sysex(bytes);
+        }
+        Ok(())
+    }
+
+    fn recv_timeout(&mut self, _timeout: Duration) -> Result<Vec<u8>, AppError> {
+        match self.incoming.pop_front() {
+            Some(bytes) => Ok(bytes),
+            None if self.connected => Err(RecvTimeoutError::Timeout.into()),
+            None => Err(RecvTimeoutError::Disconnected.into()),
+        }
+    }
+
+    fn port_name(&self) -> &str {
+        "MockMpkMini"
+    }
+}
@@ -25,20 +25,68 @@
 
 use crate::error::AppError;
 use crate::u14::U14BE;
+use crate::validate::{Problems, TEMPO_MAX, TEMPO_MIN, TEMPO_TAPS_MAX, TEMPO_TAPS_MIN};
+use std::borrow::Cow;
 use std::fmt;
+use std::str::FromStr;
+
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
 
 use serde::de;
-use serde::de::{Unexpected, Visitor};
+use serde::de::Visitor;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use serde_derive::{Deserialize, Serialize};
 
 // Note
-#[derive(Copy, Clone, Default)]
+/// MIDI note number, displayed and serialized as note name and octave (e.g., `C 4` for 60). Other octave
+/// conventions are used with [`Note::as_str_in`] and [`Note::from_str_in`].
+#[derive(Copy, Clone, Default, PartialEq, Eq)]
 pub struct Note {
     value: u8,
 }
 
+/// Octave numbering of note names: the octave of middle C (MIDI note 60).
+///
+/// `C4` is scientific pitch notation (the default), `C3` is used by e.g. Yamaha and Ableton.
+#[derive(Serialize, Deserialize, JsonSchema, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MiddleC {
+    C3,
+    #[default]
+    C4,
+    C5,
+}
+
+impl MiddleC {
+    pub fn octave(self) -> i32 {
+        match self {
+            MiddleC::C3 => 3,
+            MiddleC::C4 => 4,
+            MiddleC::C5 => 5,
+        }
+    }
+}
+
+impl FromStr for MiddleC {
+    type Err = AppError;
+
+    /// Parses `C3`, `C4`, `C5` (or just the octave).
+    fn from_str(s: &str) -> Result<Self, AppError> {
+        match s.trim().trim_start_matches(['C', 'c']) {
+            "3" => Ok(MiddleC::C3),
+            "4" => Ok(MiddleC::C4),
+            "5" => Ok(MiddleC::C5),
+            _ => Err(AppError::MiddleCInvalid(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for MiddleC {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "C{}", self.octave())
+    }
+}
+
 impl Serialize for Note {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -48,22 +96,183 @@ impl Serialize for Note {
     }
 }
 
+// Note name with accidentals (and optionally the enharmonic spelling written by Note::as_str), followed by the
+// octave
+const NOTE_PATTERN: &str = "^[A-Ga-g][#♯b♭]*(/[A-Ga-g][#♯b♭]*)? *-?[0-9]+$";
+
+/// General MIDI percussion key map, followed by common short names.
+pub const GM_DRUMS: [(&str, u8); 55] = [
+    ("Acoustic Bass Drum", 35),
+    ("Bass Drum 1", 36),
+    ("Side Stick", 37),
+    ("Acoustic Snare", 38),
+    ("Hand Clap", 39),
+    ("Electric Snare", 40),
+    ("Low Floor Tom", 41),
+    ("Closed Hi-Hat", 42),
+    ("High Floor Tom", 43),
+    ("Pedal Hi-Hat", 44),
+    ("Low Tom", 45),
+    ("Open Hi-Hat", 46),
+    ("Low-Mid Tom", 47),
+    ("Hi-Mid Tom", 48),
+    ("Crash Cymbal 1", 49),
+    ("High Tom", 50),
+    ("Ride Cymbal 1", 51),
+    ("Chinese Cymbal", 52),
+    ("Ride Bell", 53),
+    ("Tambourine", 54),
+    ("Splash Cymbal", 55),
+    ("Cowbell", 56),
+    ("Crash Cymbal 2", 57),
+    ("Vibraslap", 58),
+    ("Ride Cymbal 2", 59),
+    ("Hi Bongo", 60),
+    ("Low Bongo", 61),
+    ("Mute Hi Conga", 62),
+    ("Open Hi Conga", 63),
+    ("Low Conga", 64),
+    ("High Timbale", 65),
+    ("Low Timbale", 66),
+    ("High Agogo", 67),
+    ("Low Agogo", 68),
+    ("Cabasa", 69),
+    ("Maracas", 70),
+    ("Short Whistle", 71),
+    ("Long Whistle", 72),
+    ("Short Guiro", 73),
+    ("Long Guiro", 74),
+    ("Claves", 75),
+    ("Hi Wood Block", 76),
+    ("Low Wood Block", 77),
+    ("Mute Cuica", 78),
+    ("Open Cuica", 79),
+    ("Mute Triangle", 80),
+    ("Open Triangle", 81),
+    ("Kick", 36),
+    ("Kick 1", 36),
+    ("Kick 2", 35),
+    ("Snare", 38),
+    ("Snare 2", 40),
+    ("Clap", 39),
+    ("Rimshot", 37),
+    ("Crash", 49),
+];
+
+impl JsonSchema for Note {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        "Note".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let drums: Vec<&str> = GM_DRUMS.iter().map(|(name, _)| *name).collect();
+        json_schema!({
+            "description": "Note name and octave, e.g. `C 4` or `C#3` (60 and 49 with middle_c: C4), MIDI note \
+                number or General MIDI drum name",
+            "anyOf": [
+                { "type": "string", "pattern": NOTE_PATTERN },
+                { "type": "string", "enum": drums },
+                { "type": "integer", "minimum": 0, "maximum": 127 },
+            ],
+        })
+    }
+}
+
+/// Note as written in a file, resolved once the file's octave convention is known.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum NoteFile {
+    Name(String),
+    Number(i64),
+}
+
+impl NoteFile {
+    // Values above 127 read from the device have no name, and are written as numbers
+    pub(crate) fn new(note: Note, middle_c: MiddleC) -> Self {
+        match note.value {
+            0..=127 => NoteFile::Name(note.as_str_in(middle_c)),
+            value => NoteFile::Number(value as i64),
+        }
+    }
+
+    // Numbers are only decoded; values above 127 are reported by validation
+    pub(crate) fn resolve(&self, middle_c: MiddleC) -> Result<Note, AppError> {
+        match self {
+            NoteFile::Name(name) => Note::from_str_in(name, middle_c),
+            NoteFile::Number(value) => u8::try_from(*value)
+                .map(Note::new)
+                .map_err(|_| AppError::NoteOutOfRange(value.to_string(), *value)),
+        }
+    }
+}
+
+impl Serialize for NoteFile {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            NoteFile::Name(name) => serializer.serialize_str(name),
+            NoteFile::Number(value) => serializer.serialize_i64(*value),
+        }
+    }
+}
+
+impl JsonSchema for NoteFile {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        Note::schema_name()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        Note::json_schema(generator)
+    }
+}
+
 struct NoteVisitor;
 impl Visitor<'_> for NoteVisitor {
-    type Value = Note;
+    type Value = NoteFile;
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter.write_str("note and octave string separated by space (e.g., `B# 4`)")
+        formatter.write_str("note and octave (e.g., `C#3`, `Db -1`), MIDI note number or General MIDI drum name")
     }
 
-    fn visit_str<E>(self, value: &str) -> Result<Note, E>
+    fn visit_str<E>(self, value: &str) -> Result<NoteFile, E>
     where
         E: de::Error,
     {
-        match Note::from_str(value) {
-            Ok(n) => Ok(n),
-            Err(_) => Err(de::Error::invalid_value(Unexpected::Str(value), &self)),
-        }
+        Ok(NoteFile::Name(value.to_owned()))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<NoteFile, E>
+    where
+        E: de::Error,
+    {
+        i64::try_from(value)
+            .map(NoteFile::Number)
+            .map_err(|_| de::Error::custom(AppError::NoteOutOfRange(value.to_string(), i64::MAX)))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<NoteFile, E>
+    where
+        E: de::Error,
+    {
+        Ok(NoteFile::Number(value))
+    }
+}
+
+impl<'de> Deserialize<'de> for NoteFile {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(NoteVisitor)
     }
 }
 
@@ -72,50 +281,122 @@ impl<'de> Deserialize<'de> for Note {
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_str(NoteVisitor)
+        match NoteFile::deserialize(deserializer)? {
+            NoteFile::Number(value) => Note::from_number(value),
+            name => name.resolve(MiddleC::C4),
+        }
+        .map_err(de::Error::custom)
     }
 }
 
-impl Note {
+// Drum names match regardless of case, spaces and dashes (e.g. `closed hihat`)
+fn drum_name_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// Semitone of a note name with accidentals relative to C of the same octave (e.g. -1 for `Cb`), and the rest of
+// the string. At most a double sharp or double flat is accepted.
+fn parse_note_name(s: &str) -> Option<(i32, &str)> {
+    let semitone = match s.chars().next()?.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let mut rest = &s[1..];
+    let mut accidentals: i32 = 0;
+    while let Some(c) = rest.chars().next() {
+        let step = match c {
+            '#' | '♯' => 1,
+            'b' | '♭' => -1,
+            _ => break,
+        };
+        // Mixed accidentals (e.g. `C#b`) are rejected too
+        if accidentals * step < 0 || accidentals.abs() == 2 {
+            return None;
+        }
+        accidentals += step;
+        rest = &rest[c.len_utf8()..];
+    }
+    Some((semitone + accidentals, rest))
+}
+
+impl FromStr for Note {
+    type Err = AppError;
+
+    /// Parses a note name with accidentals (`#`, `♯`, `b`, `♭`) and octave, with or without a space (`C#3`,
+    /// `db -1`, `E♯ 2`), a MIDI note number (`60`) or a General MIDI drum name (`Kick 1`, `Snare`).
+    ///
+    /// Octaves are numbered with middle C (60) in octave 4, see [`Note::from_str_in`].
     fn from_str(s: &str) -> Result<Self, AppError> {
-        let note_octave: Vec<&str> = s.split(' ').collect();
-        if note_octave.len() != 2 {
-            return Err(AppError::NoteOctaveParse(s.to_owned()));
-        }
-
-        let note = match note_octave[0] {
-            "C" => 0,
-            "C#/Db" => 1,
-            "C#" => 1,
-            "Db" => 1,
-            "D" => 2,
-            "D#/Eb" => 3,
-            "D#" => 3,
-            "Eb" => 3,
-            "E" => 4,
-            "F" => 5,
-            "F#/Gb" => 6,
-            "F#" => 6,
-            "Gb" => 6,
-            "G" => 7,
-            "G#/Ab" => 8,
-            "G#" => 8,
-            "Ab" => 8,
-            "A" => 9,
-            "A#/Bb" => 10,
-            "A#" => 10,
-            "Bb" => 10,
-            "B" => 11,
-            _ => return Err(AppError::NoteParse(note_octave[0].to_owned(), s.to_owned())),
+        Note::from_str_in(s, MiddleC::C4)
+    }
+}
+
+impl Note {
+    pub fn new(value: u8) -> Self {
+        Note { value }
+    }
+
+    /// Parses a note like [`Note::from_str`], with octaves numbered according to `middle_c`.
+    pub fn from_str_in(s: &str, middle_c: MiddleC) -> Result<Self, AppError> {
+        let s = s.trim();
+        if let Ok(value) = s.parse::<i64>() {
+            return Note::from_number(value);
+        }
+        let key = drum_name_key(s);
+        if let Some((_, value)) = GM_DRUMS.iter().find(|(name, _)| drum_name_key(name) == key) {
+            return Ok(Note { value: *value });
+        }
+
+        let (semitone, rest) = parse_note_name(s).ok_or_else(|| AppError::NoteParse(s.to_owned()))?;
+        // Enharmonic spelling, as written by as_str (e.g., `C#/Db`)
+        let rest = match rest.strip_prefix('/') {
+            Some(alternative) => match parse_note_name(alternative) {
+                Some((other, rest)) if (other - semitone) % 12 == 0 => rest,
+                _ => return Err(AppError::NoteParse(s.to_owned())),
+            },
+            None => rest,
         };
-        let octave: i8 = note_octave[1].parse::<i8>().unwrap(); // TODO: wrap
-        Ok(Note {
-            value: ((octave + 1) * 12) as u8 + note,
-        })
+        let octave: i32 = match rest.trim_start().parse() {
+            Ok(octave) if (-2..=10).contains(&octave) => octave,
+            _ => return Err(AppError::NoteOctaveParse(s.to_owned())),
+        };
+
+        let value = (octave + 5 - middle_c.octave()) * 12 + semitone;
+        match u8::try_from(value) {
+            Ok(value) if value <= 127 => Ok(Note { value }),
+            _ => Err(AppError::NoteOutOfRange(s.to_owned(), value as i64)),
+        }
+    }
+
+    fn from_number(value: i64) -> Result<Self, AppError> {
+        match u8::try_from(value) {
+            Ok(value) if value <= 127 => Ok(Note { value }),
+            _ => Err(AppError::NoteOutOfRange(value.to_string(), value)),
+        }
     }
 
+    /// MIDI note number.
+    pub fn value(&self) -> u8 {
+        self.value
+    }
+
+    /// Note name and octave, with middle C (60) in octave 4.
     pub fn as_str(&self) -> String {
-        let octave: i8 = (self.value / 12) as i8 - 1;
+        self.as_str_in(MiddleC::C4)
+    }
+
+    /// Note name and octave, with octaves numbered according to `middle_c`.
+    pub fn as_str_in(&self, middle_c: MiddleC) -> String {
+        let octave = (self.value / 12) as i32 + middle_c.octave() - 5;
         let note = match self.value % 12 {
             0 => "C",
             1 => "C#/Db",
@@ -153,17 +434,77 @@ fn test_note_parse_str() {
     assert_eq!(1, Note::from_str("C# -1").unwrap().value);
     assert_eq!(1, Note::from_str("C#/Db -1").unwrap().value);
     assert_eq!(127, Note::from_str("G 9").unwrap().value);
+
+    for (s, value) in [
+        ("C#3", 49),
+        ("c#3", 49),
+        ("Db-1", 1),
+        ("db -1", 1),
+        ("C♯3", 49),
+        ("D♭ 3", 49),
+        ("E#4", 65),
+        ("Cb4", 59),
+        ("bb3", 58),
+        ("C##4", 62),
+        ("Dbb4", 60),
+        ("E♭♭ 4", 62),
+        ("  A 4 ", 69),
+        ("60", 60),
+        ("Kick 1", 36),
+        ("snare", 38),
+        ("closed hihat", 42),
+        ("Low-Mid Tom", 47),
+    ] {
+        assert_eq!(value, Note::from_str(s).unwrap().value, "{s}");
+    }
+
+    assert!(matches!(Note::from_str("H 4"), Err(AppError::NoteParse(_))));
+    assert!(matches!(Note::from_str("C#/Eb 4"), Err(AppError::NoteParse(_))));
+    for s in ["C###4", "Cbbb4", "D♭♭♭ 3", "C#b4", "Cb#4"] {
+        assert!(matches!(Note::from_str(s), Err(AppError::NoteParse(_))), "{s}");
+    }
+    assert!(matches!(Note::from_str("C"), Err(AppError::NoteOctaveParse(_))));
+    assert!(matches!(Note::from_str("C x"), Err(AppError::NoteOctaveParse(_))));
+    assert!(matches!(
+        Note::from_str("C 99999999999"),
+        Err(AppError::NoteOctaveParse(_))
+    ));
+    assert!(matches!(Note::from_str("G#9"), Err(AppError::NoteOutOfRange(_, 128))));
+    assert!(matches!(Note::from_str("Cb-1"), Err(AppError::NoteOutOfRange(_, -1))));
+    assert!(matches!(Note::from_str("128"), Err(AppError::NoteOutOfRange(_, 128))));
+
+    for value in 0..=127 {
+        let note = Note::new(value);
+        assert_eq!(note, Note::from_str(&note.as_str()).unwrap());
+    }
+    assert_eq!(60, serde_yaml::from_str::<Note>("60").unwrap().value);
+
+    let c3 = MiddleC::C3;
+    assert_eq!("C 3", Note::new(60).as_str_in(c3));
+    assert_eq!("C -2", Note::new(0).as_str_in(c3));
+    assert_eq!(60, Note::from_str_in("C3", c3).unwrap().value);
+    assert_eq!(127, Note::from_str_in("G 8", c3).unwrap().value);
+    assert!(matches!(
+        Note::from_str_in("G#8", c3),
+        Err(AppError::NoteOutOfRange(_, 128))
+    ));
+    assert_eq!(36, Note::from_str_in("Kick 1", c3).unwrap().value);
+    assert_eq!("C 5", Note::new(60).as_str_in(MiddleC::C5));
+    assert_eq!("C 4", Note::new(60).as_str());
+    assert_eq!(MiddleC::C3, "c3".parse().unwrap());
+    assert!(matches!("C6".parse::<MiddleC>(), Err(AppError::MiddleCInvalid(_))));
+    assert!(serde_yaml::from_str::<Note>("200").is_err());
 }
 
 // Toggle
-#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
-enum Toggle {
+#[derive(Serialize, Deserialize, JsonSchema, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Toggle {
     Off = 0,
     On = 1,
 }
 
 impl Toggle {
-    fn from(value: u8) -> Result<Self, AppError> {
+    pub(crate) fn from(value: u8) -> Result<Self, AppError> {
         match value {
             0 => Ok(Toggle::Off),
             1 => Ok(Toggle::On),
@@ -173,10 +514,14 @@ impl Toggle {
 }
 
 // Knob
-#[derive(Serialize, Deserialize, Copy, Clone, Default)]
-struct Knob {
+/// Knob assignment: control change number and value range.
+#[derive(Serialize, Deserialize, JsonSchema, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Knob {
+    #[schemars(range(max = 127))]
     control: u8,
+    #[schemars(range(max = 127))]
     min: u8,
+    #[schemars(range(max = 127))]
     max: u8,
 }
 
@@ -191,6 +536,22 @@ impl fmt::Debug for Knob {
 }
 
 impl Knob {
+    pub fn new(control: u8, min: u8, max: u8) -> Self {
+        Knob { control, min, max }
+    }
+
+    pub fn control(&self) -> u8 {
+        self.control
+    }
+
+    pub fn min(&self) -> u8 {
+        self.min
+    }
+
+    pub fn max(&self) -> u8 {
+        self.max
+    }
+
     fn from(raw: [u8; 3]) -> Self {
         Knob {
             control: raw[0],
@@ -205,15 +566,15 @@ impl Knob {
 }
 
 // PadMode
-#[derive(Default, Serialize, Deserialize, Copy, Clone, Debug)]
-enum PadMode {
+#[derive(Default, Serialize, Deserialize, JsonSchema, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PadMode {
     #[default]
     Momentary = 0,
     Toggle = 1,
 }
 
 impl PadMode {
-    fn from(value: u8) -> Result<Self, AppError> {
+    pub(crate) fn from(value: u8) -> Result<Self, AppError> {
         match value {
             0 => Ok(PadMode::Momentary),
             1 => Ok(PadMode::Toggle),
@@ -223,25 +584,94 @@ impl PadMode {
 }
 
 // Pad
-#[derive(Serialize, Deserialize, Copy, Clone, Default)]
-struct Pad {
+/// Pad assignment: note, control change and program change numbers, and pad mode.
+#[derive(Serialize, Deserialize, JsonSchema, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Pad {
     note: Note,
+    #[schemars(range(max = 127))]
     control: u8,
+    #[schemars(range(max = 127))]
     program: u8,
     mode: PadMode,
 }
 
 impl fmt::Debug for Pad {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_in(f, MiddleC::C4)
+    }
+}
+
+// Serialized form, with the note in the octave convention of the file
+/// Pad assignment: note, control change and program change numbers, and pad mode.
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(rename = "Pad")]
+pub(crate) struct PadFile {
+    note: NoteFile,
+    #[schemars(range(max = 127))]
+    control: u8,
+    #[schemars(range(max = 127))]
+    program: u8,
+    mode: PadMode,
+}
+
+impl PadFile {
+    fn new(pad: Pad, middle_c: MiddleC) -> Self {
+        PadFile {
+            note: NoteFile::new(pad.note, middle_c),
+            control: pad.control,
+            program: pad.program,
+            mode: pad.mode,
+        }
+    }
+
+    fn resolve(&self, middle_c: MiddleC) -> Result<Pad, AppError> {
+        Ok(Pad::new(
+            self.note.resolve(middle_c)?,
+            self.control,
+            self.program,
+            self.mode,
+        ))
+    }
+}
+
+impl Pad {
+    fn fmt_in(&self, f: &mut fmt::Formatter, middle_c: MiddleC) -> fmt::Result {
         write!(
             f,
             "Note: {:13}, Control: {:3}, Program: {:3}, Mode: {:?}",
-            self.note, self.control, self.program, self.mode
+            self.note.as_str_in(middle_c),
+            self.control,
+            self.program,
+            self.mode
         )
     }
-}
 
-impl Pad {
+    pub fn new(note: Note, control: u8, program: u8, mode: PadMode) -> Self {
+        Pad {
+            note,
+            control,
+            program,
+            mode,
+        }
+    }
+
+    pub fn note(&self) -> Note {
+        self.note
+    }
+
+    pub fn control(&self) -> u8 {
+        self.control
+    }
+
+    pub fn program(&self) -> u8 {
+        self.program
+    }
+
+    pub fn mode(&self) -> PadMode {
+        self.mode
+    }
+
+    // Device layout: note, program change, control change, mode
     fn from(value: [u8; 4]) -> Result<Self, AppError> {
         Ok(Pad {
             note: Note { value: value[0] },
@@ -252,19 +682,19 @@ impl Pad {
     }
 
     fn to_bytes(self) -> [u8; 4] {
-        [self.note.value, self.control, self.program, self.mode as u8]
+        [self.note.value, self.program, self.control, self.mode as u8]
     }
 }
 
 // ClockSource
-#[derive(Serialize, Deserialize, Debug)]
-enum ClockSource {
+#[derive(Serialize, Deserialize, JsonSchema, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClockSource {
     Internal,
     External,
 }
 
 impl ClockSource {
-    fn from(value: u8) -> Result<Self, AppError> {
+    pub(crate) fn from(value: u8) -> Result<Self, AppError> {
         match value {
             0 => Ok(ClockSource::Internal),
             1 => Ok(ClockSource::External),
@@ -274,8 +704,8 @@ impl ClockSource {
 }
 
 // ArpeggiatorTimeDivision
-#[derive(Serialize, Deserialize, Debug)]
-enum ArpeggiatorTimeDivision {
+#[derive(Serialize, Deserialize, JsonSchema, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ArpeggiatorTimeDivision {
     _4,
     _4T,
     _8,
@@ -287,7 +717,7 @@ enum ArpeggiatorTimeDivision {
 }
 
 impl ArpeggiatorTimeDivision {
-    fn from(value: u8) -> Result<Self, AppError> {
+    pub(crate) fn from(value: u8) -> Result<Self, AppError> {
         match value {
             0 => Ok(ArpeggiatorTimeDivision::_4),
             1 => Ok(ArpeggiatorTimeDivision::_4T),
@@ -310,8 +740,8 @@ impl fmt::Display for ArpeggiatorTimeDivision {
 }
 
 // ArpeggiatorMode
-#[derive(Serialize, Deserialize, Debug)]
-enum ArpeggiatorMode {
+#[derive(Serialize, Deserialize, JsonSchema, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ArpeggiatorMode {
     Up = 0,
     Down = 1,
     Exclusive = 2,
@@ -321,7 +751,7 @@ enum ArpeggiatorMode {
 }
 
 impl ArpeggiatorMode {
-    fn from(value: u8) -> Result<Self, AppError> {
+    pub(crate) fn from(value: u8) -> Result<Self, AppError> {
         match value {
             0 => Ok(ArpeggiatorMode::Up),
             1 => Ok(ArpeggiatorMode::Down),
@@ -335,8 +765,8 @@ impl ArpeggiatorMode {
 }
 
 // Swing
-#[derive(Serialize, Deserialize, Debug)]
-enum Swing {
+#[derive(Serialize, Deserialize, JsonSchema, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Swing {
     _50 = 0,
     _55 = 1,
     _57 = 2,
@@ -346,7 +776,7 @@ enum Swing {
 }
 
 impl Swing {
-    fn from(value: u8) -> Result<Self, AppError> {
+    pub(crate) fn from(value: u8) -> Result<Self, AppError> {
         match value {
             0 => Ok(Swing::_50),
             1 => Ok(Swing::_55),
@@ -367,11 +797,11 @@ impl fmt::Display for Swing {
 }
 
 // Joystick
-#[derive(Serialize, Deserialize, Debug)]
-enum Joystick {
+#[derive(Serialize, Deserialize, JsonSchema, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Joystick {
     Pitchbend,
-    ControlChannel(u8),
-    SplitControlChannels(u8, u8), // X: Left, Right, Y: Up, Down
+    ControlChannel(#[schemars(range(max = 127))] u8),
+    SplitControlChannels(#[schemars(range(max = 127))] u8, #[schemars(range(max = 127))] u8), // X: Left, Right, Y: Up, Down
 }
 
 impl Joystick {
@@ -384,8 +814,9 @@ impl Joystick {
         }
     }
 
-    fn to_bytes(&self) -> [u8; 3] {
-        match *self {
+    // Unused CC bytes are written as zero
+    fn to_bytes(self) -> [u8; 3] {
+        match self {
             Joystick::Pitchbend => [0; 3],
             Joystick::ControlChannel(c) => [1, c, 0],
             Joystick::SplitControlChannels(c1, c2) => [2, c1, c2],
@@ -393,22 +824,97 @@ impl Joystick {
     }
 }
 
+// Values in human units (e.g., MIDI channel 1..16), stored on the device with an offset (e.g., 0..15)
+macro_rules! device_offset_value {
+    ($(#[$doc:meta])* $name:ident, $ty:ty, $min:expr, $max:expr, device offset $offset:expr) => {
+        $(#[$doc])*
+        #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+        pub struct $name($ty);
+
+        impl $name {
+            pub const MIN: $ty = $min;
+            pub const MAX: $ty = $max;
+
+            pub fn new(value: $ty) -> Result<Self, AppError> {
+                if (Self::MIN..=Self::MAX).contains(&value) {
+                    Ok($name(value))
+                } else {
+                    Err(Self::out_of_range(value as i16))
+                }
+            }
+
+            pub fn value(&self) -> $ty {
+                self.0
+            }
+
+            fn out_of_range(value: i16) -> AppError {
+                AppError::ValueOutOfRange(stringify!($name), value, Self::MIN as i16, Self::MAX as i16)
+            }
+
+            fn from_device(value: u8) -> Result<Self, AppError> {
+                let human = value as i16 - $offset;
+                match <$ty>::try_from(human) {
+                    Ok(human) if (Self::MIN..=Self::MAX).contains(&human) => Ok($name(human)),
+                    _ => Err(AppError::DeviceValueInvalid(stringify!($name), value)),
+                }
+            }
+
+            fn to_device(self) -> u8 {
+                (self.0 as i16 + $offset) as u8
+            }
+
+            fn from_file(value: i16) -> Result<Self, AppError> {
+                Self::new(<$ty>::try_from(value).map_err(|_| Self::out_of_range(value))?)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+device_offset_value! {
+    /// Keybed octave shift, -4..+4.
+    Octave, i8, -4, 4, device offset 4
+}
+
+device_offset_value! {
+    /// Transpose in semitones, -12..+12.
+    Transpose, i8, -12, 12, device offset 12
+}
+
+device_offset_value! {
+    /// MIDI channel, 1..16.
+    MidiChannel, u8, 1, 16, device offset -1
+}
+
+device_offset_value! {
+    /// Arpeggiator octave range, 1..4.
+    ArpeggiatorOctave, u8, 1, 4, device offset -1
+}
+
 // MpkBankDescriptor
-pub(crate) const MPK_BANK_DESCRIPTOR_LENGTH: usize = 108;
+/// Length of the bank descriptor payload in device sysex messages.
+pub const MPK_BANK_DESCRIPTOR_LENGTH: usize = 108;
 
-#[derive(Serialize, Deserialize)]
+/// Settings of a single bank (or the active settings in RAM).
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+#[serde(try_from = "MpkBankDescriptorFile", into = "MpkBankDescriptorFile")]
 pub struct MpkBankDescriptor {
-    octave: u8,
-    transpose: u8, // -12 (0) .. +12 (24)
-    pad_midi_channel: u8,
-    keybed_channel: u8,
+    octave: Octave,
+    transpose: Transpose,
+    pad_midi_channel: MidiChannel,
+    keybed_channel: MidiChannel,
     joystick_x: Joystick,
     joystick_y: Joystick,
 
     arpeggiator: Toggle,
     arpeggiator_mode: ArpeggiatorMode,
     arpeggiator_time_division: ArpeggiatorTimeDivision,
-    arpeggiator_octave: u8, // 0..3
+    arpeggiator_octave: ArpeggiatorOctave,
     swing: Swing,
     latch: Toggle,
     clock_source: ClockSource,
@@ -419,36 +925,268 @@ pub struct MpkBankDescriptor {
     pads: [Pad; 16],
 }
 
+// Serialized form, with octave, transpose, channels and arpeggiator octave in human units.
+// Files in older formats are migrated by the preset module.
+/// Settings of a single bank (or the active settings in RAM).
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(rename = "MpkBankDescriptor")]
+pub(crate) struct MpkBankDescriptorFile {
+    #[schemars(range(min = Octave::MIN, max = Octave::MAX))]
+    octave: i16,
+    #[schemars(range(min = Transpose::MIN, max = Transpose::MAX))]
+    transpose: i16,
+    #[schemars(range(min = MidiChannel::MIN, max = MidiChannel::MAX))]
+    pad_midi_channel: i16,
+    #[schemars(range(min = MidiChannel::MIN, max = MidiChannel::MAX))]
+    keybed_channel: i16,
+    joystick_x: Joystick,
+    joystick_y: Joystick,
+
+    arpeggiator: Toggle,
+    arpeggiator_mode: ArpeggiatorMode,
+    arpeggiator_time_division: ArpeggiatorTimeDivision,
+    #[schemars(range(min = ArpeggiatorOctave::MIN, max = ArpeggiatorOctave::MAX))]
+    arpeggiator_octave: i16,
+    swing: Swing,
+    latch: Toggle,
+    clock_source: ClockSource,
+    #[schemars(range(min = TEMPO_TAPS_MIN, max = TEMPO_TAPS_MAX))]
+    tempo_taps: u8,
+    /// BPM
+    #[schemars(range(min = TEMPO_MIN, max = TEMPO_MAX))]
+    tempo: U14BE,
+
+    knobs: [Knob; 8],
+    pads: [PadFile; 16],
+}
+
+impl MpkBankDescriptorFile {
+    /// Serialized form of `bank_desc`, with note names in the `middle_c` convention.
+    pub(crate) fn new(bank_desc: &MpkBankDescriptor, middle_c: MiddleC) -> Self {
+        MpkBankDescriptorFile {
+            octave: bank_desc.octave.value() as i16,
+            transpose: bank_desc.transpose.value() as i16,
+            pad_midi_channel: bank_desc.pad_midi_channel.value() as i16,
+            keybed_channel: bank_desc.keybed_channel.value() as i16,
+            joystick_x: bank_desc.joystick_x,
+            joystick_y: bank_desc.joystick_y,
+            arpeggiator: bank_desc.arpeggiator,
+            arpeggiator_mode: bank_desc.arpeggiator_mode,
+            arpeggiator_time_division: bank_desc.arpeggiator_time_division,
+            arpeggiator_octave: bank_desc.arpeggiator_octave.value() as i16,
+            swing: bank_desc.swing,
+            latch: bank_desc.latch,
+            clock_source: bank_desc.clock_source,
+            tempo_taps: bank_desc.tempo_taps,
+            tempo: bank_desc.tempo,
+            knobs: bank_desc.knobs,
+            pads: bank_desc.pads.map(|pad| PadFile::new(pad, middle_c)),
+        }
+    }
+
+    // Notes are parsed before validating, since a malformed note stops parsing altogether
+    fn resolve_pads(&self, middle_c: MiddleC) -> Result<[Pad; 16], AppError> {
+        let mut pads = [Pad::default(); 16];
+        for (pad, file) in pads.iter_mut().zip(&self.pads) {
+            *pad = file.resolve(middle_c)?;
+        }
+        Ok(pads)
+    }
+
+    /// Checks all fields, with note names in the `middle_c` convention, returning every problem found as
+    /// [`AppError::ValidationFailed`].
+    pub(crate) fn validate(&self, middle_c: MiddleC) -> Result<(), AppError> {
+        self.check(&self.resolve_pads(middle_c)?)
+    }
+
+    fn check(&self, pads: &[Pad; 16]) -> Result<(), AppError> {
+        let mut problems = Problems::default();
+        let mut human = |path: &str, value: i16, min: i16, max: i16| {
+            problems.range(path.to_owned(), value as i64, min as i64, max as i64)
+        };
+        human("octave", self.octave, Octave::MIN as i16, Octave::MAX as i16);
+        human(
+            "transpose",
+            self.transpose,
+            Transpose::MIN as i16,
+            Transpose::MAX as i16,
+        );
+        human(
+            "pad_midi_channel",
+            self.pad_midi_channel,
+            MidiChannel::MIN as i16,
+            MidiChannel::MAX as i16,
+        );
+        human(
+            "keybed_channel",
+            self.keybed_channel,
+            MidiChannel::MIN as i16,
+            MidiChannel::MAX as i16,
+        );
+        for (path, joystick) in [("joystick_x", self.joystick_x), ("joystick_y", self.joystick_y)] {
+            match joystick {
+                Joystick::Pitchbend => (),
+                Joystick::ControlChannel(c) => problems.midi_data(path.to_owned(), c),
+                Joystick::SplitControlChannels(c1, c2) => {
+                    problems.midi_data(format!("{path}[0]"), c1);
+                    problems.midi_data(format!("{path}[1]"), c2);
+                }
+            }
+        }
+        problems.range(
+            "arpeggiator_octave".to_owned(),
+            self.arpeggiator_octave as i64,
+            ArpeggiatorOctave::MIN as i64,
+            ArpeggiatorOctave::MAX as i64,
+        );
+        problems.range(
+            "tempo_taps".to_owned(),
+            self.tempo_taps as i64,
+            TEMPO_TAPS_MIN,
+            TEMPO_TAPS_MAX,
+        );
+        problems.range("tempo".to_owned(), self.tempo.value() as i64, TEMPO_MIN, TEMPO_MAX);
+
+        for (i, knob) in self.knobs.iter().enumerate() {
+            problems.midi_data(format!("knobs[{i}].control"), knob.control);
+            problems.midi_data(format!("knobs[{i}].min"), knob.min);
+            problems.midi_data(format!("knobs[{i}].max"), knob.max);
+            if knob.min > knob.max {
+                problems.push(
+                    format!("knobs[{i}]"),
+                    format!("min {} greater than max {}", knob.min, knob.max),
+                );
+            }
+        }
+        for (i, pad) in pads.iter().enumerate() {
+            problems.midi_data(format!("pads[{i}].note"), pad.note.value);
+            problems.midi_data(format!("pads[{i}].control"), pad.control);
+            problems.midi_data(format!("pads[{i}].program"), pad.program);
+        }
+        problems.into_result()
+    }
+
+    /// Settings read from a file with note names in the `middle_c` convention.
+    ///
+    /// Values are only decoded, so that settings read from the device (e.g. in backups) are read back as
+    /// they are; see [`MpkBankDescriptorFile::validate`] for user input.
+    pub(crate) fn into_bank_desc(self, middle_c: MiddleC) -> Result<MpkBankDescriptor, AppError> {
+        let pads = self.resolve_pads(middle_c)?;
+        Ok(MpkBankDescriptor {
+            octave: Octave::from_file(self.octave)?,
+            transpose: Transpose::from_file(self.transpose)?,
+            pad_midi_channel: MidiChannel::from_file(self.pad_midi_channel)?,
+            keybed_channel: MidiChannel::from_file(self.keybed_channel)?,
+            joystick_x: self.joystick_x,
+            joystick_y: self.joystick_y,
+            arpeggiator: self.arpeggiator,
+            arpeggiator_mode: self.arpeggiator_mode,
+            arpeggiator_time_division: self.arpeggiator_time_division,
+            arpeggiator_octave: ArpeggiatorOctave::from_file(self.arpeggiator_octave)?,
+            swing: self.swing,
+            latch: self.latch,
+            clock_source: self.clock_source,
+            tempo_taps: self.tempo_taps,
+            tempo: self.tempo,
+            knobs: self.knobs,
+            pads,
+        })
+    }
+}
+
+impl TryFrom<MpkBankDescriptorFile> for MpkBankDescriptor {
+    type Error = AppError;
+
+    fn try_from(file: MpkBankDescriptorFile) -> Result<Self, AppError> {
+        file.into_bank_desc(MiddleC::C4)
+    }
+}
+
+impl From<MpkBankDescriptor> for MpkBankDescriptorFile {
+    fn from(bank_desc: MpkBankDescriptor) -> Self {
+        MpkBankDescriptorFile::new(&bank_desc, MiddleC::C4)
+    }
+}
+
 impl fmt::Display for MpkBankDescriptor {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "PAD Channel: {}", self.pad_midi_channel + 1)?;
-        writeln!(f, "Keybed Channel: {}", self.keybed_channel + 1)?;
-        writeln!(f, "Octave: {}", self.octave as i8 - 4)?;
-        writeln!(f, "Transpose: {}", self.transpose as i8 - 12)?;
-        writeln!(f, "Arpeggiator: {:?}", self.arpeggiator)?;
-        writeln!(f, "Arpeggiator Mode: {:?}", self.arpeggiator_mode)?;
-        writeln!(f, "Arpeggiator Time Division: {}", self.arpeggiator_time_division)?;
-        writeln!(f, "Arpeggiator Tempo: {}", self.tempo)?;
-        writeln!(f, "Arpeggiator Octave: {}", self.arpeggiator_octave + 1)?;
-        writeln!(f, "Swing: {}", self.swing)?;
-        writeln!(f, "Clock source: {:?}", self.clock_source)?;
-        writeln!(f, "Latch: {:?}", self.latch)?;
-        writeln!(f, "Tempo taps: {}", self.tempo_taps)?;
-        writeln!(f, "Joystick X: {:?}", self.joystick_x)?;
-        writeln!(f, "Joystick Y: {:?}", self.joystick_y)?;
+        self.display_in(MiddleC::C4).fmt(f)
+    }
+}
 
-        for (i, knob) in self.knobs.iter().enumerate() {
+/// Displays settings with note names in a given octave convention, see [`MpkBankDescriptor::display_in`].
+pub struct BankDisplay<'a> {
+    bank_desc: &'a MpkBankDescriptor,
+    middle_c: MiddleC,
+}
+
+impl fmt::Display for BankDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bank_desc = self.bank_desc;
+        writeln!(f, "PAD Channel: {}", bank_desc.pad_midi_channel)?;
+        writeln!(f, "Keybed Channel: {}", bank_desc.keybed_channel)?;
+        writeln!(f, "Octave: {}", bank_desc.octave)?;
+        writeln!(f, "Transpose: {}", bank_desc.transpose)?;
+        writeln!(f, "Arpeggiator: {:?}", bank_desc.arpeggiator)?;
+        writeln!(f, "Arpeggiator Mode: {:?}", bank_desc.arpeggiator_mode)?;
+        writeln!(f, "Arpeggiator Time Division: {}", bank_desc.arpeggiator_time_division)?;
+        writeln!(f, "Arpeggiator Tempo: {}", bank_desc.tempo)?;
+        writeln!(f, "Arpeggiator Octave: {}", bank_desc.arpeggiator_octave)?;
+        writeln!(f, "Swing: {}", bank_desc.swing)?;
+        writeln!(f, "Clock source: {:?}", bank_desc.clock_source)?;
+        writeln!(f, "Latch: {:?}", bank_desc.latch)?;
+        writeln!(f, "Tempo taps: {}", bank_desc.tempo_taps)?;
+        writeln!(f, "Joystick X: {:?}", bank_desc.joystick_x)?;
+        writeln!(f, "Joystick Y: {:?}", bank_desc.joystick_y)?;
+
+        for (i, knob) in bank_desc.knobs.iter().enumerate() {
             writeln!(f, "Knob {}: {:?}", i + 1, knob)?;
         }
 
-        for (i, pad) in self.pads.iter().enumerate() {
+        for (i, pad) in bank_desc.pads.iter().enumerate() {
             let padbank = if i < 8 { "A" } else { "B" };
-            writeln!(f, "Pad {}{}: {:?}", padbank, i % 8 + 1, pad)?;
+            write!(f, "Pad {}{}: ", padbank, i % 8 + 1)?;
+            pad.fmt_in(f, self.middle_c)?;
+            writeln!(f)?;
         }
         Ok(())
     }
 }
 
+impl Default for MpkBankDescriptor {
+    fn default() -> Self {
+        let mut knobs = [Knob::default(); 8];
+        for (i, knob) in knobs.iter_mut().enumerate() {
+            *knob = Knob::new(i as u8 + 1, 0, 127);
+        }
+
+        let mut pads = [Pad::default(); 16];
+        for (i, pad) in pads.iter_mut().enumerate() {
+            *pad = Pad::new(Note::new(36 + i as u8), i as u8 + 1, i as u8, PadMode::Momentary);
+        }
+
+        MpkBankDescriptor {
+            octave: Octave(0),
+            transpose: Transpose(0),
+            pad_midi_channel: MidiChannel(10),
+            keybed_channel: MidiChannel(1),
+            joystick_x: Joystick::Pitchbend,
+            joystick_y: Joystick::ControlChannel(1),
+            arpeggiator: Toggle::Off,
+            arpeggiator_mode: ArpeggiatorMode::Up,
+            arpeggiator_time_division: ArpeggiatorTimeDivision::_16,
+            arpeggiator_octave: ArpeggiatorOctave(1),
+            swing: Swing::_50,
+            latch: Toggle::Off,
+            clock_source: ClockSource::Internal,
+            tempo_taps: 3,
+            tempo: U14BE::new(120).unwrap(),
+            knobs,
+            pads,
+        }
+    }
+}
+
 impl fmt::Debug for MpkBankDescriptor {
     /* TODO: write raw bytes */
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -456,7 +1194,74 @@ impl fmt::Debug for MpkBankDescriptor {
     }
 }
 
+// Typed getter and builder (`with_*`) for each scalar field
+macro_rules! bank_descriptor_accessors {
+    ($($field:ident, $with:ident: $ty:ty;)*) => {
+        impl MpkBankDescriptor {
+            $(
+                pub fn $field(&self) -> $ty {
+                    self.$field
+                }
+
+                pub fn $with(mut self, $field: $ty) -> Self {
+                    self.$field = $field;
+                    self
+                }
+            )*
+        }
+    };
+}
+
+bank_descriptor_accessors! {
+    octave, with_octave: Octave;
+    transpose, with_transpose: Transpose;
+    pad_midi_channel, with_pad_midi_channel: MidiChannel;
+    keybed_channel, with_keybed_channel: MidiChannel;
+    joystick_x, with_joystick_x: Joystick;
+    joystick_y, with_joystick_y: Joystick;
+    arpeggiator, with_arpeggiator: Toggle;
+    arpeggiator_mode, with_arpeggiator_mode: ArpeggiatorMode;
+    arpeggiator_time_division, with_arpeggiator_time_division: ArpeggiatorTimeDivision;
+    arpeggiator_octave, with_arpeggiator_octave: ArpeggiatorOctave;
+    swing, with_swing: Swing;
+    latch, with_latch: Toggle;
+    clock_source, with_clock_source: ClockSource;
+    tempo_taps, with_tempo_taps: u8;
+    tempo, with_tempo: U14BE;
+}
+
 impl MpkBankDescriptor {
+    pub fn knobs(&self) -> &[Knob; 8] {
+        &self.knobs
+    }
+
+    pub fn with_knobs(mut self, knobs: [Knob; 8]) -> Self {
+        self.knobs = knobs;
+        self
+    }
+
+    /// Replaces a single knob (0-based index).
+    pub fn with_knob(mut self, index: usize, knob: Knob) -> Result<Self, AppError> {
+        *self.knobs.get_mut(index).ok_or(AppError::KnobIndexOutOfBounds(index))? = knob;
+        Ok(self)
+    }
+
+    /// Pads A1..A8 followed by B1..B8.
+    pub fn pads(&self) -> &[Pad; 16] {
+        &self.pads
+    }
+
+    pub fn with_pads(mut self, pads: [Pad; 16]) -> Self {
+        self.pads = pads;
+        self
+    }
+
+    /// Replaces a single pad (0-based index, A1..A8 followed by B1..B8).
+    pub fn with_pad(mut self, index: usize, pad: Pad) -> Result<Self, AppError> {
+        *self.pads.get_mut(index).ok_or(AppError::PadIndexOutOfBounds(index))? = pad;
+        Ok(self)
+    }
+
     fn parse_knobs(bytes: &[u8]) -> Result<[Knob; 8], AppError> {
         if bytes.len() != 8 * 3 {
             Err(AppError::BankKnobsUnexpectedLength(bytes.len()))
@@ -481,14 +1286,15 @@ impl MpkBankDescriptor {
         }
     }
 
+    /// Parses the 108 byte device representation.
     pub fn from(bytes: &[u8]) -> Result<Self, AppError> {
         if bytes.len() != MPK_BANK_DESCRIPTOR_LENGTH {
             Err(AppError::BankDescriptionUnexpectedLength(bytes.len()))
         } else {
             Ok(MpkBankDescriptor {
-                pad_midi_channel: bytes[0],
-                keybed_channel: bytes[1],
-                octave: bytes[2],
+                pad_midi_channel: MidiChannel::from_device(bytes[0])?,
+                keybed_channel: MidiChannel::from_device(bytes[1])?,
+                octave: Octave::from_device(bytes[2])?,
                 arpeggiator: Toggle::from(bytes[3])?,
                 arpeggiator_mode: ArpeggiatorMode::from(bytes[4])?,
                 arpeggiator_time_division: ArpeggiatorTimeDivision::from(bytes[5])?,
@@ -497,21 +1303,39 @@ impl MpkBankDescriptor {
                 swing: Swing::from(bytes[8])?,
                 tempo_taps: bytes[9],
                 tempo: U14BE::from_device([bytes[10], bytes[11]])?,
-                arpeggiator_octave: bytes[12],
+                arpeggiator_octave: ArpeggiatorOctave::from_device(bytes[12])?,
                 joystick_x: Joystick::from([bytes[13], bytes[14], bytes[15]])?,
                 joystick_y: Joystick::from([bytes[16], bytes[17], bytes[18]])?,
                 pads: MpkBankDescriptor::parse_pads(&bytes[19..83])?,
                 knobs: MpkBankDescriptor::parse_knobs(&bytes[83..107])?,
-                transpose: bytes[107],
+                transpose: Transpose::from_device(bytes[107])?,
             })
         }
     }
 
-    pub fn into_bytes(self) -> Vec<u8> {
+    /// Checks that all values are accepted by the device, returning every problem found as
+    /// [`AppError::ValidationFailed`].
+    pub fn validate(&self) -> Result<(), AppError> {
+        MpkBankDescriptorFile::new(self, MiddleC::C4).validate(MiddleC::C4)
+    }
+
+    /// Displays the settings with note names in the `middle_c` convention.
+    pub fn display_in(&self, middle_c: MiddleC) -> BankDisplay<'_> {
+        BankDisplay {
+            bank_desc: self,
+            middle_c,
+        }
+    }
+
+    /// Encodes into the 108 byte device representation.
+    ///
+    /// Values are not validated, so that settings read from the device are written back as they
+    /// are; user input is validated separately (see [`MpkBankDescriptor::validate`]).
+    pub fn into_bytes(self) -> Result<Vec<u8>, AppError> {
         let mut ret: Vec<u8> = vec![
-            self.pad_midi_channel,
-            self.keybed_channel,
-            self.octave,
+            self.pad_midi_channel.to_device(),
+            self.keybed_channel.to_device(),
+            self.octave.to_device(),
             self.arpeggiator as u8,
             self.arpeggiator_mode as u8,
             self.arpeggiator_time_division as u8,
@@ -520,8 +1344,8 @@ impl MpkBankDescriptor {
             self.swing as u8,
             self.tempo_taps,
         ];
-        append_array!(ret, self.tempo.to_device().unwrap());
-        ret.push(self.arpeggiator_octave);
+        append_array!(ret, self.tempo.to_device()?);
+        ret.push(self.arpeggiator_octave.to_device());
         append_array!(ret, self.joystick_x.to_bytes());
         append_array!(ret, self.joystick_y.to_bytes());
         for pad in &self.pads {
@@ -530,9 +1354,63 @@ impl MpkBankDescriptor {
         for knob in &self.knobs {
             append_array!(ret, knob.to_bytes());
         }
-        ret.push(self.transpose);
+        ret.push(self.transpose.to_device());
 
         assert_eq!(ret.len(), MPK_BANK_DESCRIPTOR_LENGTH);
-        ret
+        Ok(ret)
     }
 }
+
+#[test]
+fn test_bank_descriptor_builder() {
+    let bank_desc = MpkBankDescriptor::default()
+        .with_octave(Octave::new(-2).unwrap())
+        .with_arpeggiator_mode(ArpeggiatorMode::Random)
+        .with_knob(2, Knob::new(74, 0, 127))
+        .unwrap();
+    assert_eq!(-2, bank_desc.octave().value());
+    assert_eq!(ArpeggiatorMode::Random, bank_desc.arpeggiator_mode());
+    assert_eq!(74, bank_desc.knobs()[2].control());
+    assert!(bank_desc.with_pad(16, Pad::default()).is_err());
+}
+
+#[test]
+fn test_bank_descriptor_human_units() {
+    let bank_desc = MpkBankDescriptor::default()
+        .with_octave(Octave::new(-4).unwrap())
+        .with_transpose(Transpose::new(12).unwrap())
+        .with_keybed_channel(MidiChannel::new(16).unwrap());
+    let bytes = bank_desc.clone().into_bytes().unwrap();
+    assert_eq!([9, 15, 0], bytes[0..3]);
+    assert_eq!(24, bytes[107]);
+
+    let yaml = serde_yaml::to_string(&bank_desc).unwrap();
+    assert!(yaml.starts_with("octave: -4\ntranspose: 12\npad_midi_channel: 10\n"));
+    assert!(serde_yaml::from_str::<MpkBankDescriptor>(&yaml.replace("octave: -4", "octave: -5")).is_err());
+    assert!(yaml.contains("\ntempo: 120\n"));
+    let nested = serde_yaml::from_str::<MpkBankDescriptor>(&yaml.replace("tempo: 120", "tempo: {host: 90}"));
+    assert_eq!(90, nested.unwrap().tempo().value());
+    // Values the device does not accept are read as they are, and reported by validation
+    let fast = serde_yaml::from_str::<MpkBankDescriptor>(&yaml.replace("tempo: 120", "tempo: 300")).unwrap();
+    assert_eq!(300, fast.tempo().value());
+    assert!(matches!(fast.validate(), Err(AppError::ValidationFailed(p)) if p[0].path == "tempo"));
+    let note = serde_yaml::from_str::<MpkBankDescriptor>(&yaml.replacen("note: C 2", "note: 200", 1)).unwrap();
+    assert_eq!(200, note.pads()[0].note().value());
+    assert_eq!(
+        note,
+        serde_yaml::from_str(&serde_yaml::to_string(&note).unwrap()).unwrap()
+    );
+
+    // Pads are stored as note, program, control, mode
+    let pad = Pad::new(Note::new(40), 20, 30, PadMode::Toggle);
+    let bytes = bank_desc.with_pad(0, pad).unwrap().into_bytes().unwrap();
+    assert_eq!([40, 30, 20, 1], bytes[19..23]);
+    assert_eq!(pad, MpkBankDescriptor::from(&bytes).unwrap().pads()[0]);
+
+    assert!(Octave::new(5).is_err());
+    assert!(MidiChannel::new(0).is_err());
+    assert!(matches!(
+        Octave::from_device(9),
+        Err(AppError::DeviceValueInvalid("Octave", 9))
+    ));
+}
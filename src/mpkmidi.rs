@@ -23,13 +23,43 @@
  *
  */
 
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
+
 use crate::{error::AppError, mpkbank::MpkBankDescriptor};
 
 // https://www.midi.org/specifications/item/table-1-summary-of-midi-message
-const MIDI_SYSEX: u8 = 0xf0;
-const MIDI_SYSEX_END: u8 = 0xf7;
-const SYSEX_AKAI: u8 = 0x47; // See http://www.amei.or.jp/report/System_ID_e.html
-const MIDI_RESET: u8 = 0xff;
+pub(crate) const MIDI_SYSEX: u8 = 0xf0;
+pub(crate) const MIDI_SYSEX_END: u8 = 0xf7;
+pub(crate) const SYSEX_AKAI: u8 = 0x47; // See http://www.amei.or.jp/report/System_ID_e.html
+
+// System common
+const MIDI_MTC_QUARTER_FRAME: u8 = 0xf1;
+const MIDI_SONG_POSITION: u8 = 0xf2;
+const MIDI_SONG_SELECT: u8 = 0xf3;
+const MIDI_TUNE_REQUEST: u8 = 0xf6;
+
+// System real time
+pub(crate) const MIDI_TIMING_CLOCK: u8 = 0xf8;
+const MIDI_START: u8 = 0xfa;
+const MIDI_CONTINUE: u8 = 0xfb;
+const MIDI_STOP: u8 = 0xfc;
+const MIDI_ACTIVE_SENSING: u8 = 0xfe;
+pub(crate) const MIDI_RESET: u8 = 0xff;
+
+// Sysex IDs: universal messages and the first byte of three byte manufacturer IDs
+const SYSEX_NON_REAL_TIME: u8 = 0x7e;
+const SYSEX_REAL_TIME: u8 = 0x7f;
+const SYSEX_EXTENDED_ID: u8 = 0x00;
+
+// Universal non-real time general information messages (sub-ID#1 0x06), sent to all devices (0x7f)
+const SYSEX_ALL_CALL: u8 = 0x7f;
+const SYSEX_GENERAL_INFORMATION: u8 = 0x06;
+const SYSEX_IDENTITY_REQUEST: u8 = 0x01;
+const SYSEX_IDENTITY_REPLY: u8 = 0x02;
+
+/// Family code of the MPK Mini mkII in identity replies (the product ID of its AKAI sysex headers).
+pub const MPK_MINI_MK2_FAMILY: u16 = 0x26;
 
 // Channel messages are in the form 0xMC, where M = message type and C = channel
 const MIDI_NOTE_OFF: u8 = 0x80;
@@ -41,51 +71,238 @@ const MIDI_CHANNEL_PRESSURE: u8 = 0xd0;
 const MIDI_PITCH_BEND: u8 = 0xe0;
 
 // MPK-Specific
-const SYSEX_MPK_BANK: [u8; 5] = [0x00, 0x26, 0x67, 0x00, 0x6d];
+pub(crate) const SYSEX_MPK_BANK: [u8; 5] = [0x00, 0x26, 0x67, 0x00, 0x6d];
+pub(crate) const SYSEX_MPK_GET_BANK: [u8; 5] = [0x00, 0x26, 0x66, 0x00, 0x01];
+pub(crate) const SYSEX_MPK_SET_BANK: [u8; 5] = [0x00, 0x26, 0x64, 0x00, 0x6d];
+
+fn sysex_mpk(header: [u8; 5], bank: u8, payload: &[u8]) -> Vec<u8> {
+    let mut ret = vec![MIDI_SYSEX, SYSEX_AKAI];
+    append_array!(ret, header);
+    ret.push(bank);
+    append_array!(ret, payload);
+    ret.push(MIDI_SYSEX_END);
+    ret
+}
+
+/// Sysex request for the settings of `bank` (0 = RAM); the device replies with [`MpkMidiMessage::Bank`].
 pub fn sysex_get_bank(bank: u8) -> Vec<u8> {
+    sysex_mpk(SYSEX_MPK_GET_BANK, bank, &[])
+}
+
+/// Sysex message writing `bank_desc` to `bank` (0 = RAM).
+pub fn sysex_set_bank(bank: u8, bank_desc: MpkBankDescriptor) -> Result<Vec<u8>, AppError> {
+    Ok(sysex_mpk(SYSEX_MPK_SET_BANK, bank, &bank_desc.into_bytes()?))
+}
+
+/// Universal Identity Request, answered by devices with [`MpkMidiMessage::IdentityReply`].
+pub fn sysex_identity_request() -> Vec<u8> {
     vec![
         MIDI_SYSEX,
-        SYSEX_AKAI,
-        0x00,
-        0x26,
-        0x66,
-        0x00,
-        0x01,
-        bank,
+        SYSEX_NON_REAL_TIME,
+        SYSEX_ALL_CALL,
+        SYSEX_GENERAL_INFORMATION,
+        SYSEX_IDENTITY_REQUEST,
         MIDI_SYSEX_END,
     ]
 }
 
-pub fn sysex_set_bank(bank: u8, bank_desc: MpkBankDescriptor) -> Vec<u8> {
-    let mut ret = vec![MIDI_SYSEX, SYSEX_AKAI, 0x00, 0x26, 0x64, 0x00, 0x6d, bank];
-    append_array!(ret, &bank_desc.into_bytes());
-    ret.push(MIDI_SYSEX_END);
-    ret
+// Device reply to sysex_get_bank, with the raw bank descriptor bytes
+pub(crate) fn sysex_bank(bank: u8, bank_bytes: &[u8]) -> Vec<u8> {
+    sysex_mpk(SYSEX_MPK_BANK, bank, bank_bytes)
 }
 
-// u14, little endian, only needed for snoop.
+// u14, little endian (pitch bend, song position)
 macro_rules! u14le_to_u16 {
     ($x:expr, $offset:expr) => {
         $x[$offset] as u16 + (($x[$offset + 1] as u16) << 7)
     };
 }
 
-#[derive(Debug)]
-#[allow(unused)]
+fn u16_to_u14le(value: u16) -> Result<[u8; 2], AppError> {
+    if value > 0x3fff {
+        Err(AppError::U14BEValueTooLarge(value))
+    } else {
+        Ok([(value & 0x7f) as u8, (value >> 7) as u8])
+    }
+}
+
+// Checks a data byte (MSB unset) for serialization
+fn data_byte(value: u8) -> Result<u8, AppError> {
+    match value & 0x80 {
+        0 => Ok(value),
+        _ => Err(AppError::MidiDataInvalid(value)),
+    }
+}
+
+fn channel_status(status: u8, channel: u8) -> Result<u8, AppError> {
+    match channel {
+        0..=15 => Ok(status | channel),
+        _ => Err(AppError::MidiChannelInvalid(channel)),
+    }
+}
+
+// Length of messages with `status` (other than sysex), including the status byte
+fn message_length(status: u8) -> usize {
+    match status & 0xf0 {
+        MIDI_PROGRAM_CHANGE | MIDI_CHANNEL_PRESSURE => 2,
+        0x80..=0xe0 => 3,
+        _ => match status {
+            MIDI_SONG_POSITION => 3,
+            MIDI_MTC_QUARTER_FRAME | MIDI_SONG_SELECT => 2,
+            // Real time, tune request and undefined status bytes carry no data
+            _ => 1,
+        },
+    }
+}
+
+/// Manufacturer ID of a system exclusive message.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SysExId {
+    /// One byte ID, e.g. 0x47 (AKAI)
+    Manufacturer(u8),
+    /// Three byte ID: 0x00 followed by two bytes
+    Extended(u8, u8),
+    /// Universal non-real time message (0x7e)
+    NonRealTime,
+    /// Universal real time message (0x7f)
+    RealTime,
+}
+
+impl SysExId {
+    // Parses the ID at the start of a sysex payload, returning it with the rest of the payload
+    fn parse(payload: &[u8]) -> Option<(Self, &[u8])> {
+        match payload {
+            [SYSEX_EXTENDED_ID, id1, id2, data @ ..] => Some((SysExId::Extended(*id1, *id2), data)),
+            [SYSEX_EXTENDED_ID, ..] => None,
+            [SYSEX_NON_REAL_TIME, data @ ..] => Some((SysExId::NonRealTime, data)),
+            [SYSEX_REAL_TIME, data @ ..] => Some((SysExId::RealTime, data)),
+            [id, data @ ..] => Some((SysExId::Manufacturer(*id), data)),
+            [] => None,
+        }
+    }
+
+    fn to_bytes(self) -> Result<Vec<u8>, AppError> {
+        match self {
+            SysExId::Manufacturer(SYSEX_EXTENDED_ID | SYSEX_NON_REAL_TIME | SYSEX_REAL_TIME) => {
+                Err(AppError::SysEx(format!("reserved manufacturer ID {self:?}")))
+            }
+            SysExId::Manufacturer(id) => Ok(vec![data_byte(id)?]),
+            SysExId::Extended(id1, id2) => Ok(vec![SYSEX_EXTENDED_ID, data_byte(id1)?, data_byte(id2)?]),
+            SysExId::NonRealTime => Ok(vec![SYSEX_NON_REAL_TIME]),
+            SysExId::RealTime => Ok(vec![SYSEX_REAL_TIME]),
+        }
+    }
+}
+
+/// Device model and firmware, as reported in the reply to [`sysex_identity_request`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DeviceIdentity {
+    /// Sysex device ID (channel) of the replying device
+    pub device_id: u8,
+    pub manufacturer: SysExId,
+    pub family: u16,
+    pub model: u16,
+    /// Software revision, as four data bytes
+    pub firmware: [u8; 4],
+}
+
+impl DeviceIdentity {
+    /// Whether bank settings can be written to this device.
+    pub fn is_supported(&self) -> bool {
+        self.manufacturer == SysExId::Manufacturer(SYSEX_AKAI) && self.family == MPK_MINI_MK2_FAMILY
+    }
+
+    // Parses the data of a universal non-real time message following the device ID and sub-IDs
+    fn parse(device_id: u8, data: &[u8]) -> Result<Self, AppError> {
+        let malformed = || AppError::SysEx(format!("malformed identity reply {data:?}"));
+        let (manufacturer, data) = SysExId::parse(data).ok_or_else(malformed)?;
+        match (manufacturer, data) {
+            (SysExId::Manufacturer(_) | SysExId::Extended(..), [family @ .., model_lsb, model_msb, v1, v2, v3, v4])
+                if family.len() == 2 =>
+            {
+                Ok(DeviceIdentity {
+                    device_id,
+                    manufacturer,
+                    family: u14le_to_u16!(family, 0),
+                    model: *model_lsb as u16 + ((*model_msb as u16) << 7),
+                    firmware: [*v1, *v2, *v3, *v4],
+                })
+            }
+            _ => Err(malformed()),
+        }
+    }
+
+    fn to_bytes(self) -> Result<Vec<u8>, AppError> {
+        let mut ret = vec![
+            MIDI_SYSEX,
+            SYSEX_NON_REAL_TIME,
+            data_byte(self.device_id)?,
+            SYSEX_GENERAL_INFORMATION,
+            SYSEX_IDENTITY_REPLY,
+        ];
+        ret.extend(self.manufacturer.to_bytes()?);
+        ret.extend(u16_to_u14le(self.family)?);
+        ret.extend(u16_to_u14le(self.model)?);
+        for value in self.firmware {
+            ret.push(data_byte(value)?);
+        }
+        ret.push(MIDI_SYSEX_END);
+        Ok(ret)
+    }
+}
+
+impl fmt::Display for DeviceIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let manufacturer = match self.manufacturer {
+            SysExId::Manufacturer(SYSEX_AKAI) => "AKAI (0x47)".to_owned(),
+            SysExId::Manufacturer(id) => format!("{id:#04x}"),
+            SysExId::Extended(id1, id2) => format!("0x00 {id1:#04x} {id2:#04x}"),
+            SysExId::NonRealTime | SysExId::RealTime => format!("{:?}", self.manufacturer),
+        };
+        writeln!(f, "Manufacturer: {manufacturer}")?;
+        writeln!(f, "Family: {:#06x}", self.family)?;
+        writeln!(f, "Model: {:#06x}", self.model)?;
+        let [v1, v2, v3, v4] = self.firmware;
+        writeln!(f, "Firmware: {v1}.{v2}.{v3}.{v4}")?;
+        write!(f, "Device ID: {}", self.device_id)
+    }
+}
+
+/// MIDI 1.0 message, as received from the device.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MpkMidiMessage {
     // channel, note, velocity
     NoteOff(u8, u8, u8),
     NoteOn(u8, u8, u8),
+    // channel, note, pressure
+    PolyphonicPressure(u8, u8, u8),
     // channel, control, value
     ControlChange(u8, u8, u8),
     ProgramChange(u8, u8),
+    // channel, pressure
+    ChannelPressure(u8, u8),
     PitchBend(u8, u16),
-    // Unparsed
-    Unparsed,
-    // System
+    // System common
+    // message type (0-7), value (0-15)
+    MtcQuarterFrame(u8, u8),
+    // MIDI beats (sixteenth notes) since the start of the song
+    SongPosition(u16),
+    SongSelect(u8),
+    TuneRequest,
+    // System real time
+    TimingClock,
+    Start,
+    Continue,
+    Stop,
+    ActiveSensing,
     Reset,
     // MPKmini2-specific
     Bank(u8, MpkBankDescriptor),
+    // Universal Identity Reply
+    IdentityReply(DeviceIdentity),
+    // Other system exclusive messages: ID and data (without the ID and F0/F7)
+    SysEx(SysExId, Vec<u8>),
+    // Undefined status bytes (0xf4, 0xf5, 0xf9, 0xfd)
     Unknown(Vec<u8>),
 }
 
@@ -97,44 +314,86 @@ impl MpkMidiMessage {
         if *bytes.last().unwrap() != MIDI_SYSEX_END {
             return Err(AppError::SysEx(format!("malformed: {bytes:?}")));
         }
-        if bytes[1] != SYSEX_AKAI {
-            return Err(AppError::SysEx(format!(
-                "non-AKAI: (manufacturer={:x}, expected={:x}) {:?}",
-                bytes[1], SYSEX_AKAI, bytes
-            )));
-        }
 
-        let payload = &bytes[2..bytes.len() - 1];
-        if payload.starts_with(&SYSEX_MPK_BANK) {
-            Ok(MpkMidiMessage::Bank(
-                payload[SYSEX_MPK_BANK.len()],
-                MpkBankDescriptor::from(&payload[SYSEX_MPK_BANK.len() + 1..])?,
-            ))
-        } else {
-            Err(AppError::SysEx(format!("unknown AKAI sysex message {payload:?}")))
+        let payload = &bytes[1..bytes.len() - 1];
+        if let Some(&data) = payload.iter().find(|&&data| data & 0x80 != 0) {
+            return Err(AppError::MidiDataInvalid(data));
+        }
+        let (id, data) =
+            SysExId::parse(payload).ok_or_else(|| AppError::SysEx(format!("truncated manufacturer ID: {bytes:?}")))?;
+        match id {
+            SysExId::Manufacturer(SYSEX_AKAI) => match data.strip_prefix(&SYSEX_MPK_BANK) {
+                Some(bank_msg) => {
+                    let (&bank, bank_bytes) = bank_msg
+                        .split_first()
+                        .ok_or_else(|| AppError::SysEx(format!("truncated bank message {payload:?}")))?;
+                    Ok(MpkMidiMessage::Bank(bank, MpkBankDescriptor::from(bank_bytes)?))
+                }
+                None => Ok(MpkMidiMessage::SysEx(id, data.to_vec())),
+            },
+            SysExId::NonRealTime => match data {
+                [device_id, SYSEX_GENERAL_INFORMATION, SYSEX_IDENTITY_REPLY, reply @ ..] => {
+                    Ok(MpkMidiMessage::IdentityReply(DeviceIdentity::parse(*device_id, reply)?))
+                }
+                _ => Ok(MpkMidiMessage::SysEx(id, data.to_vec())),
+            },
+            _ => Ok(MpkMidiMessage::SysEx(id, data.to_vec())),
         }
     }
 
     fn parse_channel_msg(bytes: &[u8]) -> Result<Self, AppError> {
+        let expected = message_length(bytes[0]);
+        if bytes.len() != expected {
+            return Err(AppError::MidiMessageLength(bytes[0], bytes.len(), expected));
+        }
+        if let Some(&data) = bytes[1..].iter().find(|&&data| data & 0x80 != 0) {
+            return Err(AppError::MidiDataInvalid(data));
+        }
+
         let channel = bytes[0] & 0x0f;
         match bytes[0] & 0xf0 {
             MIDI_NOTE_OFF => Ok(MpkMidiMessage::NoteOff(channel, bytes[1], bytes[2])),
             MIDI_NOTE_ON => Ok(MpkMidiMessage::NoteOn(channel, bytes[1], bytes[2])),
-            MIDI_POLYPHONIC_PRESSURE => Ok(MpkMidiMessage::Unparsed),
+            MIDI_POLYPHONIC_PRESSURE => Ok(MpkMidiMessage::PolyphonicPressure(channel, bytes[1], bytes[2])),
             MIDI_CONTROL_CHANGE => Ok(MpkMidiMessage::ControlChange(channel, bytes[1], bytes[2])),
             MIDI_PROGRAM_CHANGE => Ok(MpkMidiMessage::ProgramChange(channel, bytes[1])),
-            MIDI_CHANNEL_PRESSURE => Ok(MpkMidiMessage::Unparsed),
+            MIDI_CHANNEL_PRESSURE => Ok(MpkMidiMessage::ChannelPressure(channel, bytes[1])),
             MIDI_PITCH_BEND => Ok(MpkMidiMessage::PitchBend(channel, u14le_to_u16!(bytes, 1))),
             _ => unreachable!(),
         }
     }
 
+    fn parse_system_msg(bytes: &[u8]) -> Result<Self, AppError> {
+        let expected = message_length(bytes[0]);
+        if bytes.len() != expected {
+            return Err(AppError::MidiMessageLength(bytes[0], bytes.len(), expected));
+        }
+        if let Some(&data) = bytes[1..].iter().find(|&&data| data & 0x80 != 0) {
+            return Err(AppError::MidiDataInvalid(data));
+        }
+
+        Ok(match bytes[0] {
+            MIDI_MTC_QUARTER_FRAME => MpkMidiMessage::MtcQuarterFrame(bytes[1] >> 4, bytes[1] & 0x0f),
+            MIDI_SONG_POSITION => MpkMidiMessage::SongPosition(u14le_to_u16!(bytes, 1)),
+            MIDI_SONG_SELECT => MpkMidiMessage::SongSelect(bytes[1]),
+            MIDI_TUNE_REQUEST => MpkMidiMessage::TuneRequest,
+            MIDI_TIMING_CLOCK => MpkMidiMessage::TimingClock,
+            MIDI_START => MpkMidiMessage::Start,
+            MIDI_CONTINUE => MpkMidiMessage::Continue,
+            MIDI_STOP => MpkMidiMessage::Stop,
+            MIDI_ACTIVE_SENSING => MpkMidiMessage::ActiveSensing,
+            MIDI_RESET => MpkMidiMessage::Reset,
+            _ => MpkMidiMessage::Unknown(Vec::from(bytes)),
+        })
+    }
+
+    /// Parses a single complete MIDI message.
     pub fn parse_msg(bytes: &[u8]) -> Result<Self, AppError> {
         if bytes.is_empty() {
             return Err(AppError::SysExEmptyMessage);
         }
 
-        if bytes[0] < 127 {
+        if bytes[0] & 0x80 == 0 {
             return Err(AppError::SysExMsbUnset);
         }
 
@@ -144,8 +403,461 @@ impl MpkMidiMessage {
 
         match bytes[0] {
             MIDI_SYSEX => MpkMidiMessage::parse_sysex(bytes),
-            MIDI_RESET => Ok(MpkMidiMessage::Reset),
-            _ => Ok(MpkMidiMessage::Unknown(Vec::from(bytes))),
+            MIDI_SYSEX_END => Err(AppError::SysEx("end of exclusive without start".to_owned())),
+            _ => MpkMidiMessage::parse_system_msg(bytes),
+        }
+    }
+
+    /// Encodes the message, failing if a value does not fit its field (e.g. a data byte above 127) or
+    /// bank settings are not accepted by the device.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AppError> {
+        let channel_msg = |status: u8, channel: u8, data: &[u8]| -> Result<Vec<u8>, AppError> {
+            let mut ret = vec![channel_status(status, channel)?];
+            for &value in data {
+                ret.push(data_byte(value)?);
+            }
+            Ok(ret)
+        };
+        match self {
+            MpkMidiMessage::NoteOff(channel, note, velocity) => {
+                channel_msg(MIDI_NOTE_OFF, *channel, &[*note, *velocity])
+            }
+            MpkMidiMessage::NoteOn(channel, note, velocity) => channel_msg(MIDI_NOTE_ON, *channel, &[*note, *velocity]),
+            MpkMidiMessage::PolyphonicPressure(channel, note, pressure) => {
+                channel_msg(MIDI_POLYPHONIC_PRESSURE, *channel, &[*note, *pressure])
+            }
+            MpkMidiMessage::ControlChange(channel, control, value) => {
+                channel_msg(MIDI_CONTROL_CHANGE, *channel, &[*control, *value])
+            }
+            MpkMidiMessage::ProgramChange(channel, program) => channel_msg(MIDI_PROGRAM_CHANGE, *channel, &[*program]),
+            MpkMidiMessage::ChannelPressure(channel, pressure) => {
+                channel_msg(MIDI_CHANNEL_PRESSURE, *channel, &[*pressure])
+            }
+            MpkMidiMessage::PitchBend(channel, value) => channel_msg(MIDI_PITCH_BEND, *channel, &u16_to_u14le(*value)?),
+            MpkMidiMessage::MtcQuarterFrame(message_type, value) => {
+                if *message_type > 7 {
+                    return Err(AppError::MidiDataInvalid(*message_type));
+                }
+                if *value > 0x0f {
+                    return Err(AppError::MidiDataInvalid(*value));
+                }
+                Ok(vec![MIDI_MTC_QUARTER_FRAME, (message_type << 4) | value])
+            }
+            MpkMidiMessage::SongPosition(beats) => {
+                let [lsb, msb] = u16_to_u14le(*beats)?;
+                Ok(vec![MIDI_SONG_POSITION, lsb, msb])
+            }
+            MpkMidiMessage::SongSelect(song) => Ok(vec![MIDI_SONG_SELECT, data_byte(*song)?]),
+            MpkMidiMessage::TuneRequest => Ok(vec![MIDI_TUNE_REQUEST]),
+            MpkMidiMessage::TimingClock => Ok(vec![MIDI_TIMING_CLOCK]),
+            MpkMidiMessage::Start => Ok(vec![MIDI_START]),
+            MpkMidiMessage::Continue => Ok(vec![MIDI_CONTINUE]),
+            MpkMidiMessage::Stop => Ok(vec![MIDI_STOP]),
+            MpkMidiMessage::ActiveSensing => Ok(vec![MIDI_ACTIVE_SENSING]),
+            MpkMidiMessage::Reset => Ok(vec![MIDI_RESET]),
+            MpkMidiMessage::Bank(bank, bank_desc) => {
+                Ok(sysex_bank(data_byte(*bank)?, &bank_desc.clone().into_bytes()?))
+            }
+            MpkMidiMessage::IdentityReply(identity) => identity.to_bytes(),
+            MpkMidiMessage::SysEx(id, data) => {
+                let mut ret = vec![MIDI_SYSEX];
+                ret.extend(id.to_bytes()?);
+                for &value in data {
+                    ret.push(data_byte(value)?);
+                }
+                ret.push(MIDI_SYSEX_END);
+                Ok(ret)
+            }
+            MpkMidiMessage::Unknown(bytes) => match MpkMidiMessage::parse_msg(bytes)? {
+                MpkMidiMessage::Unknown(_) => Ok(bytes.clone()),
+                other => Err(AppError::SysEx(format!("not an undefined message: {other:?}"))),
+            },
+        }
+    }
+}
+
+/// Decodes MIDI messages from a byte stream (e.g. a raw MIDI file, `amidi` dump, serial link or
+/// stdin) delivered in chunks of any size.
+///
+/// Handles running status, real time messages interleaved with other messages (also within sysex)
+/// and sysex messages spanning several chunks. Malformed input (e.g. data bytes without a status, or
+/// a sysex message interrupted by another status byte) is reported as an error in place of the
+/// message, and decoding continues with the next status byte.
+#[derive(Debug, Default)]
+pub struct MidiStreamParser {
+    // Message being received, starting with its status byte
+    pending: Vec<u8>,
+    // Status of the last channel message, reused by messages without a status byte
+    running_status: Option<u8>,
+}
+
+impl MidiStreamParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes `bytes`, returning the messages completed by them.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<Result<MpkMidiMessage, AppError>> {
+        bytes.iter().flat_map(|&byte| self.push_byte(byte)).collect()
+    }
+
+    /// Decodes a single byte, returning the messages it completes: none, one, or for a status byte
+    /// interrupting a message, the error for the interrupted message followed by the message the
+    /// status byte completes on its own (e.g. a tune request).
+    pub fn push_byte(&mut self, byte: u8) -> Vec<Result<MpkMidiMessage, AppError>> {
+        match byte {
+            // Real time messages may appear anywhere, without affecting the message being received
+            MIDI_TIMING_CLOCK..=MIDI_RESET => vec![MpkMidiMessage::parse_msg(&[byte])],
+            MIDI_SYSEX_END => match self.pending.first() {
+                Some(&MIDI_SYSEX) => {
+                    self.pending.push(byte);
+                    vec![MpkMidiMessage::parse_msg(&std::mem::take(&mut self.pending))]
+                }
+                _ => {
+                    let mut results = self.interrupted();
+                    results.push(Err(AppError::SysEx("end of exclusive without start".to_owned())));
+                    results
+                }
+            },
+            0x80..=0xff => {
+                let mut results = self.interrupted();
+                // Only channel messages set the running status; system messages cancel it
+                self.running_status = (byte < MIDI_SYSEX).then_some(byte);
+                self.pending.push(byte);
+                results.extend(self.complete());
+                results
+            }
+            _ => {
+                if self.pending.is_empty() {
+                    match self.running_status {
+                        Some(status) => self.pending.push(status),
+                        None => return vec![Err(AppError::MidiDataWithoutStatus(byte))],
+                    }
+                }
+                self.pending.push(byte);
+                self.complete().into_iter().collect()
+            }
+        }
+    }
+
+    /// Ends the stream, returning an error if a message is incomplete.
+    pub fn finish(&mut self) -> Result<(), AppError> {
+        self.running_status = None;
+        self.interrupt()
+    }
+
+    // Discards the message being received (when a new status byte arrives), failing if it is incomplete
+    fn interrupt(&mut self) -> Result<(), AppError> {
+        let pending = std::mem::take(&mut self.pending);
+        match pending.first() {
+            None => Ok(()),
+            Some(&MIDI_SYSEX) => Err(AppError::SysEx(format!("unterminated: {pending:?}"))),
+            Some(&status) => Err(AppError::MidiMessageLength(
+                status,
+                pending.len(),
+                message_length(status),
+            )),
+        }
+    }
+
+    // The error for the message discarded by a new status byte, if it was incomplete
+    fn interrupted(&mut self) -> Vec<Result<MpkMidiMessage, AppError>> {
+        self.interrupt().err().into_iter().map(Err).collect()
+    }
+
+    // Parses the message being received once it has all its data bytes
+    fn complete(&mut self) -> Option<Result<MpkMidiMessage, AppError>> {
+        match self.pending[0] {
+            MIDI_SYSEX => None,
+            status if self.pending.len() < message_length(status) => None,
+            _ => Some(MpkMidiMessage::parse_msg(&std::mem::take(&mut self.pending))),
+        }
+    }
+}
+
+/// Decodes the messages of a raw MIDI byte stream, or with `hex` of whitespace separated hex bytes (e.g.
+/// `amidi -d` output), passing each message, or error in place of a malformed one, to `f`.
+///
+/// A message left incomplete at the end of the stream is passed as an error.
+pub fn decode_stream<R: Read>(
+    reader: R,
+    hex: bool,
+    mut f: impl FnMut(Result<MpkMidiMessage, AppError>),
+) -> Result<(), AppError> {
+    let mut parser = MidiStreamParser::new();
+    let mut push = |chunk: &[u8]| parser.push(chunk).into_iter().for_each(&mut f);
+    if hex {
+        for line in BufReader::new(reader).lines() {
+            let bytes = line?
+                .split_whitespace()
+                .map(|byte| u8::from_str_radix(byte, 16).map_err(|_| AppError::HexByteInvalid(byte.to_owned())))
+                .collect::<Result<Vec<_>, _>>()?;
+            push(&bytes);
+        }
+    } else {
+        let mut reader = reader;
+        let mut chunk = [0; 1024];
+        loop {
+            match reader.read(&mut chunk)? {
+                0 => break,
+                len => push(&chunk[..len]),
+            }
         }
     }
+    if let Err(e) = parser.finish() {
+        f(Err(e));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_parse_malformed_msg() {
+    assert!(matches!(
+        MpkMidiMessage::parse_msg(&[0x90, 60, 100]),
+        Ok(MpkMidiMessage::NoteOn(0, 60, 100))
+    ));
+    assert!(matches!(
+        MpkMidiMessage::parse_msg(&[0xc3, 5]),
+        Ok(MpkMidiMessage::ProgramChange(3, 5))
+    ));
+
+    assert!(matches!(
+        MpkMidiMessage::parse_msg(&[]),
+        Err(AppError::SysExEmptyMessage)
+    ));
+    assert!(matches!(
+        MpkMidiMessage::parse_msg(&[0x7f, 1]),
+        Err(AppError::SysExMsbUnset)
+    ));
+    for truncated in [&[0x90][..], &[0x90, 60], &[0xb0, 1], &[0xe0, 0], &[0xc0], &[0xc0, 1, 2]] {
+        assert!(matches!(
+            MpkMidiMessage::parse_msg(truncated),
+            Err(AppError::MidiMessageLength(..))
+        ));
+    }
+    assert!(matches!(
+        MpkMidiMessage::parse_msg(&[0x80, 0x90, 0]),
+        Err(AppError::MidiDataInvalid(0x90))
+    ));
+
+    let bank = sysex_bank(1, &MpkBankDescriptor::default().into_bytes().unwrap());
+    assert!(matches!(
+        MpkMidiMessage::parse_msg(&bank),
+        Ok(MpkMidiMessage::Bank(1, _))
+    ));
+    for len in [7, 8, 100] {
+        let mut truncated = bank[..len].to_vec();
+        truncated.push(MIDI_SYSEX_END);
+        assert!(MpkMidiMessage::parse_msg(&truncated).is_err());
+    }
+}
+
+#[test]
+fn test_msg_round_trip() {
+    let messages = [
+        (MpkMidiMessage::NoteOff(1, 60, 0), vec![0x81, 60, 0]),
+        (MpkMidiMessage::NoteOn(9, 36, 127), vec![0x99, 36, 127]),
+        (MpkMidiMessage::PolyphonicPressure(0, 60, 64), vec![0xa0, 60, 64]),
+        (MpkMidiMessage::ControlChange(15, 1, 100), vec![0xbf, 1, 100]),
+        (MpkMidiMessage::ProgramChange(2, 5), vec![0xc2, 5]),
+        (MpkMidiMessage::ChannelPressure(3, 90), vec![0xd3, 90]),
+        (MpkMidiMessage::PitchBend(0, 0x2000), vec![0xe0, 0x00, 0x40]),
+        (MpkMidiMessage::MtcQuarterFrame(7, 3), vec![0xf1, 0x73]),
+        (MpkMidiMessage::SongPosition(300), vec![0xf2, 0x2c, 0x02]),
+        (MpkMidiMessage::SongSelect(4), vec![0xf3, 4]),
+        (MpkMidiMessage::TuneRequest, vec![0xf6]),
+        (MpkMidiMessage::TimingClock, vec![0xf8]),
+        (MpkMidiMessage::Start, vec![0xfa]),
+        (MpkMidiMessage::Continue, vec![0xfb]),
+        (MpkMidiMessage::Stop, vec![0xfc]),
+        (MpkMidiMessage::ActiveSensing, vec![0xfe]),
+        (MpkMidiMessage::Reset, vec![0xff]),
+        (MpkMidiMessage::Unknown(vec![0xf5]), vec![0xf5]),
+        (
+            MpkMidiMessage::SysEx(SysExId::NonRealTime, vec![0x7f, 0x06, 0x01]),
+            vec![0xf0, 0x7e, 0x7f, 0x06, 0x01, 0xf7],
+        ),
+        (
+            MpkMidiMessage::SysEx(SysExId::Manufacturer(0x41), vec![0x10, 0x42]),
+            vec![0xf0, 0x41, 0x10, 0x42, 0xf7],
+        ),
+        (
+            MpkMidiMessage::SysEx(SysExId::Extended(0x20, 0x29), vec![1]),
+            vec![0xf0, 0x00, 0x20, 0x29, 0x01, 0xf7],
+        ),
+        (
+            MpkMidiMessage::SysEx(
+                SysExId::Manufacturer(SYSEX_AKAI),
+                SYSEX_MPK_GET_BANK.into_iter().chain([1]).collect(),
+            ),
+            sysex_get_bank(1),
+        ),
+        (
+            MpkMidiMessage::Bank(2, MpkBankDescriptor::default()),
+            sysex_bank(2, &MpkBankDescriptor::default().into_bytes().unwrap()),
+        ),
+    ];
+    for (msg, bytes) in messages {
+        assert_eq!(bytes, msg.to_bytes().unwrap(), "{msg:?}");
+        assert_eq!(msg, MpkMidiMessage::parse_msg(&bytes).unwrap());
+    }
+
+    assert!(matches!(
+        MpkMidiMessage::NoteOn(16, 60, 1).to_bytes(),
+        Err(AppError::MidiChannelInvalid(16))
+    ));
+    assert!(matches!(
+        MpkMidiMessage::ControlChange(0, 128, 1).to_bytes(),
+        Err(AppError::MidiDataInvalid(128))
+    ));
+    assert!(MpkMidiMessage::PitchBend(0, 0x4000).to_bytes().is_err());
+    assert!(MpkMidiMessage::MtcQuarterFrame(8, 0).to_bytes().is_err());
+    assert!(MpkMidiMessage::SysEx(SysExId::Manufacturer(0x7e), vec![])
+        .to_bytes()
+        .is_err());
+    assert!(MpkMidiMessage::Unknown(vec![0xf8]).to_bytes().is_err());
+    assert!(MpkMidiMessage::parse_msg(&[0xf0, 0x00, 0x20, 0xf7]).is_err());
+    assert!(MpkMidiMessage::parse_msg(&[0xf0, 0x41, 0x80, 0xf7]).is_err());
+    assert!(MpkMidiMessage::parse_msg(&[0xf2, 1]).is_err());
+    assert!(MpkMidiMessage::parse_msg(&[0xf8, 1]).is_err());
+}
+
+#[test]
+fn test_stream_parser() {
+    let mut parser = MidiStreamParser::new();
+    let parse = |parser: &mut MidiStreamParser, bytes: &[u8]| -> Vec<MpkMidiMessage> {
+        parser.push(bytes).into_iter().map(Result::unwrap).collect()
+    };
+
+    // Running status, split across chunks, with interleaved timing clocks
+    assert_eq!(
+        vec![MpkMidiMessage::NoteOn(0, 60, 100), MpkMidiMessage::TimingClock],
+        parse(&mut parser, &[0x90, 60, 100, 62, 0xf8])
+    );
+    assert_eq!(
+        vec![MpkMidiMessage::NoteOn(0, 62, 0), MpkMidiMessage::ProgramChange(1, 3)],
+        parse(&mut parser, &[0, 0xc1, 3])
+    );
+    assert_eq!(vec![MpkMidiMessage::ProgramChange(1, 4)], parse(&mut parser, &[4]));
+
+    // Sysex across chunks, with a real time message inside
+    let bank = sysex_bank(3, &MpkBankDescriptor::default().into_bytes().unwrap());
+    assert!(parse(&mut parser, &bank[..50]).is_empty());
+    assert_eq!(vec![MpkMidiMessage::Stop], parse(&mut parser, &[0xfc]));
+    assert_eq!(
+        vec![MpkMidiMessage::Bank(3, MpkBankDescriptor::default())],
+        parse(&mut parser, &bank[50..])
+    );
+    // System messages cancel running status
+    assert!(matches!(
+        parser.push(&[5])[..],
+        [Err(AppError::MidiDataWithoutStatus(5))]
+    ));
+    assert_eq!(
+        vec![MpkMidiMessage::TuneRequest, MpkMidiMessage::SongPosition(128)],
+        parse(&mut parser, &[0xf6, 0xf2, 0, 1])
+    );
+    assert!(parser.finish().is_ok());
+
+    // Interrupted messages are reported, and decoding continues with the new status
+    let results = parser.push(&[0xf0, 0x41, 1, 0x90, 60, 1, 0xb0, 7, 0x80, 60, 0]);
+    assert!(matches!(results[0], Err(AppError::SysEx(_))));
+    assert_eq!(MpkMidiMessage::NoteOn(0, 60, 1), *results[1].as_ref().unwrap());
+    assert!(matches!(results[2], Err(AppError::MidiMessageLength(0xb0, 2, 3))));
+    assert_eq!(MpkMidiMessage::NoteOff(0, 60, 0), *results[3].as_ref().unwrap());
+    assert_eq!(4, results.len());
+    assert!(matches!(parser.push(&[0xf7])[..], [Err(AppError::SysEx(_))]));
+
+    // One byte messages interrupting a message are decoded after the error
+    assert!(matches!(
+        parser.push(&[0x90, 60, 0xf6])[..],
+        [
+            Err(AppError::MidiMessageLength(0x90, 2, 3)),
+            Ok(MpkMidiMessage::TuneRequest)
+        ]
+    ));
+    assert!(matches!(
+        parser.push(&[0xf0, 0x41, 1, 0xf6])[..],
+        [Err(AppError::SysEx(_)), Ok(MpkMidiMessage::TuneRequest)]
+    ));
+    let results = parser.push(&[0xb0, 7, 0xf4]);
+    assert_eq!(2, results.len());
+    assert!(matches!(results[0], Err(AppError::MidiMessageLength(0xb0, 2, 3))));
+    assert!(matches!(
+        parser.push(&[0xc0, 0xf7])[..],
+        [Err(AppError::MidiMessageLength(0xc0, 1, 2)), Err(AppError::SysEx(_))]
+    ));
+    assert!(parser.push(&[0xe0, 1]).is_empty());
+    assert!(matches!(parser.finish(), Err(AppError::MidiMessageLength(0xe0, 2, 3))));
+}
+
+#[test]
+fn test_decode_stream() {
+    let decode = |bytes: &[u8], hex: bool| {
+        let mut results = Vec::new();
+        decode_stream(bytes, hex, |result| results.push(result)).map(|()| results)
+    };
+    let results = decode(b"90 3c 64\nf8\n\n80 3c", true).unwrap();
+    assert_eq!(MpkMidiMessage::NoteOn(0, 60, 100), *results[0].as_ref().unwrap());
+    assert_eq!(MpkMidiMessage::TimingClock, *results[1].as_ref().unwrap());
+    assert!(matches!(results[2], Err(AppError::MidiMessageLength(0x80, 2, 3))));
+    assert_eq!(3, results.len());
+    assert!(matches!(decode(b"90 zz", true), Err(AppError::HexByteInvalid(b)) if b == "zz"));
+
+    let results = decode(&[0xc0, 1, 2], false).unwrap();
+    assert_eq!(2, results.len());
+    assert_eq!(MpkMidiMessage::ProgramChange(0, 2), *results[1].as_ref().unwrap());
+}
+
+#[test]
+fn test_identity() {
+    assert_eq!(
+        MpkMidiMessage::SysEx(SysExId::NonRealTime, vec![0x7f, 0x06, 0x01]),
+        MpkMidiMessage::parse_msg(&sysex_identity_request()).unwrap()
+    );
+
+    let reply = [
+        0xf0, 0x7e, 0x00, 0x06, 0x02, 0x47, 0x26, 0x00, 0x19, 0x00, 0x01, 0x00, 0x05, 0x00, 0xf7,
+    ];
+    let identity = match MpkMidiMessage::parse_msg(&reply).unwrap() {
+        MpkMidiMessage::IdentityReply(identity) => identity,
+        other => panic!("unexpected {other:?}"),
+    };
+    assert_eq!(
+        DeviceIdentity {
+            device_id: 0,
+            manufacturer: SysExId::Manufacturer(SYSEX_AKAI),
+            family: MPK_MINI_MK2_FAMILY,
+            model: 0x19,
+            firmware: [1, 0, 5, 0],
+        },
+        identity
+    );
+    assert!(identity.is_supported());
+    assert_eq!(
+        reply.to_vec(),
+        MpkMidiMessage::IdentityReply(identity).to_bytes().unwrap()
+    );
+    assert!(identity.to_string().contains("Firmware: 1.0.5.0"));
+
+    // MPK Mini mk3
+    let mut mk3 = reply;
+    mk3[6] = 0x49;
+    assert!(matches!(
+        MpkMidiMessage::parse_msg(&mk3),
+        Ok(MpkMidiMessage::IdentityReply(identity)) if !identity.is_supported()
+    ));
+    // Three byte manufacturer ID
+    let extended = [
+        0xf0, 0x7e, 0x10, 0x06, 0x02, 0x00, 0x20, 0x29, 0x01, 0x02, 0x03, 0x04, 1, 2, 3, 4, 0xf7,
+    ];
+    assert!(matches!(
+        MpkMidiMessage::parse_msg(&extended),
+        Ok(MpkMidiMessage::IdentityReply(DeviceIdentity {
+            manufacturer: SysExId::Extended(0x20, 0x29),
+            family: 0x101,
+            ..
+        }))
+    ));
+    assert!(MpkMidiMessage::parse_msg(&reply[..12].iter().chain(&[0xf7]).copied().collect::<Vec<_>>()).is_err());
 }
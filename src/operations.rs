@@ -23,122 +23,526 @@
  *
  */
 
-use std::sync::mpsc;
-use std::thread::sleep;
-use std::time::Duration;
+use std::io::Write;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant, SystemTime};
 
+use crate::assignment::{apply_assignments, Assignment};
+use crate::backup::{Backup, BackupBank, BackupMetadata};
+use crate::diff::{diff_bank_desc, FieldDiff};
 use crate::error::*;
 
 use log::{debug, error, info, warn};
 
-use crate::mpkbank::MpkBankDescriptor;
+use crate::mpkbank::{MiddleC, MpkBankDescriptor};
 use crate::mpkmidi::*;
-use crate::util::*;
-
-pub fn snoop() -> Result<(), AppError> {
-    let cb = |_, bytes: &[u8], _: &mut _| {
-        debug!("rx bytes: {:?}", bytes);
-        match MpkMidiMessage::parse_msg(bytes) {
-            Ok(m) => println!("{m:?}"),
-            Err(e) => warn!("Unparsed: {}; bytes: {:?}", e, bytes),
-        }
-    };
-    let _midi_in = midi_in_connect(cb, ())?;
-    info!("Snoop started. Use CTRL-C to stop.");
-    loop {
-        sleep(Duration::from_millis(250));
+use crate::preset::{Preset, PRESET_MODEL, PRESET_VERSION};
+use crate::transport::MidiTransport;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const BANK_REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+const IDENTITY_REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn print_msg(bytes: &[u8]) {
+    match MpkMidiMessage::parse_msg(bytes) {
+        Ok(m) => println!("{m:?}"),
+        Err(e) => warn!("Unparsed: {}; bytes: {:?}", e, bytes),
     }
 }
 
-pub fn passthrough() -> Result<(), AppError> {
-    let (tx, rx) = mpsc::channel();
-
-    let cb = move |_, bytes: &[u8], _: &mut _| {
-        debug!("rx bytes: {:?}", bytes);
-        match MpkMidiMessage::parse_msg(bytes) {
-            Ok(m) => println!("{m:?}"),
-            Err(e) => warn!("Unparsed: {}; bytes: {:?}", e, bytes),
-        }
-        if let Err(e) = tx.send(Vec::from(bytes)) {
-            error!("Error while sending: {}", e);
+/// Prints MIDI messages received from the device until interrupted.
+pub fn snoop<T: MidiTransport>(transport: &mut T) -> Result<(), AppError> {
+    info!("Snoop started. Use CTRL-C to stop.");
+    loop {
+        match transport.recv_timeout(POLL_INTERVAL) {
+            Ok(bytes) => print_msg(&bytes),
+            Err(AppError::MpscRecvTimeoutError(RecvTimeoutError::Timeout)) => continue,
+            Err(e) => return Err(e),
         }
-    };
-
-    let mut midi_out = midi_out_connect()?;
-    let _midi_in = midi_in_connect(cb, ())?;
+    }
+}
 
+/// Forwards MIDI messages from the device input to its output (while snooping) until interrupted.
+pub fn passthrough<T: MidiTransport>(transport: &mut T) -> Result<(), AppError> {
     info!("Passthrough started: MIDI messages from input will be sent to output. Use CTRL-C to stop.");
     loop {
-        match rx.recv() {
-            Ok(m) => {
-                if let Err(e) = midi_out.send(m.as_slice()) {
+        match transport.recv_timeout(POLL_INTERVAL) {
+            Ok(bytes) => {
+                print_msg(&bytes);
+                if let Err(e) = transport.send(&bytes) {
                     error!("Error while forwarding: {}", e);
                 }
             }
-            Err(e) => {
-                error!("Error while receiving: {}", e);
-            }
+            Err(AppError::MpscRecvTimeoutError(RecvTimeoutError::Timeout)) => continue,
+            Err(e) => return Err(e),
         }
     }
 }
 
-pub fn get_bank_desc(bank: u8) -> Result<MpkBankDescriptor, AppError> {
+// Requests `bank` and returns the raw reply message along with the parsed settings
+fn recv_bank<T: MidiTransport>(transport: &mut T, bank: u8) -> Result<(Vec<u8>, MpkBankDescriptor), AppError> {
     if bank > 4 {
         return Err(AppError::BankIndexOutOfBounds(bank));
     }
 
-    let (tx, rx) = mpsc::channel();
+    transport.send(sysex_get_bank(bank).as_slice())?;
 
-    let cb = move |_, bytes: &[u8], _: &mut _| {
-        if let Ok(m) = MpkMidiMessage::parse_msg(bytes) {
-            if let MpkMidiMessage::Bank(bank_rx, d) = m {
+    let deadline = Instant::now() + BANK_REPLY_TIMEOUT;
+    loop {
+        let bytes = transport.recv_timeout(deadline.saturating_duration_since(Instant::now()))?;
+        match MpkMidiMessage::parse_msg(&bytes) {
+            Ok(MpkMidiMessage::Bank(bank_rx, d)) => {
                 if bank != bank_rx {
                     error!("Error: received bank {}, expected {}", bank_rx, bank);
                 }
-                if let Err(e) = tx.send(d) {
-                    error!("Error while sending on channel: {}", e);
-                }
-            } else {
-                warn!("Unexpected message (ignored): {:?}", m);
+                return Ok((bytes, d));
             }
-        } else {
-            warn!("Unparsed: {:?}", bytes);
+            Ok(m) => warn!("Unexpected message (ignored): {:?}", m),
+            Err(_) => warn!("Unparsed: {:?}", bytes),
         }
-    };
+    }
+}
 
-    let mut midi_out = midi_out_connect()?;
-    let midi_in = midi_in_connect(cb, ())?;
+/// Reads the settings of `bank` (0 = RAM) from the device.
+pub fn get_bank_desc<T: MidiTransport>(transport: &mut T, bank: u8) -> Result<MpkBankDescriptor, AppError> {
+    Ok(recv_bank(transport, bank)?.1)
+}
+
+/// Reads `bank` (0 = RAM) from the device and returns the complete sysex message as received.
+pub fn get_bank_sysex<T: MidiTransport>(transport: &mut T, bank: u8) -> Result<Vec<u8>, AppError> {
+    Ok(recv_bank(transport, bank)?.0)
+}
+
+/// Sends the universal Identity Request and waits for the device's reply.
+pub fn identify<T: MidiTransport>(transport: &mut T) -> Result<DeviceIdentity, AppError> {
+    transport.send(&sysex_identity_request())?;
+
+    let deadline = Instant::now() + IDENTITY_REPLY_TIMEOUT;
+    loop {
+        let bytes = transport.recv_timeout(deadline.saturating_duration_since(Instant::now()))?;
+        match MpkMidiMessage::parse_msg(&bytes) {
+            Ok(MpkMidiMessage::IdentityReply(identity)) => return Ok(identity),
+            Ok(m) => warn!("Unexpected message (ignored): {:?}", m),
+            Err(_) => warn!("Unparsed: {:?}", bytes),
+        }
+    }
+}
 
-    midi_out.send(sysex_get_bank(bank).as_slice())?;
-    let bank_desc = rx.recv_timeout(Duration::new(10, 0))?;
+/// Prints the identity the device reports.
+pub fn show_identity<T: MidiTransport>(transport: &mut T) -> Result<(), AppError> {
+    let identity = match identify(transport) {
+        Err(AppError::MpscRecvTimeoutError(RecvTimeoutError::Timeout)) => {
+            return Err(AppError::DeviceUnidentified(transport.port_name().to_owned()))
+        }
+        result => result?,
+    };
+    println!("{identity}");
+    println!("Supported: {}", if identity.is_supported() { "yes" } else { "no" });
+    Ok(())
+}
 
-    midi_out.close();
-    midi_in.close();
+/// Identifies the device before writing to it, failing if it reports a model other than the MPK Mini mkII.
+///
+/// Devices that do not answer the identity request are written to, with a warning.
+fn check_supported<T: MidiTransport>(transport: &mut T) -> Result<(), AppError> {
+    match identify(transport) {
+        Ok(identity) if identity.is_supported() => {
+            debug!("Identified device: {:?}", identity);
+            Ok(())
+        }
+        Ok(identity) => Err(AppError::DeviceUnsupported(identity.to_string())),
+        Err(AppError::MpscRecvTimeoutError(RecvTimeoutError::Timeout)) => {
+            warn!("No identity reply from '{}', writing anyway", transport.port_name());
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
 
-    Ok(bank_desc)
+// Writes a bank to a device already checked with check_supported
+fn send_bank<T: MidiTransport>(transport: &mut T, bank: u8, bank_desc: MpkBankDescriptor) -> Result<(), AppError> {
+    transport.send(&sysex_set_bank(bank, bank_desc)?)
 }
 
-pub fn set_bank_from_desc(bank: u8, bank_desc: MpkBankDescriptor) -> Result<(), AppError> {
+/// Writes `bank_desc` to `bank` (0 = RAM) on the device.
+///
+/// Fails if the device identifies itself as an unsupported model.
+pub fn set_bank_from_desc<T: MidiTransport>(
+    transport: &mut T,
+    bank: u8,
+    bank_desc: MpkBankDescriptor,
+) -> Result<(), AppError> {
     if bank > 4 {
         return Err(AppError::BankIndexOutOfBounds(bank));
     }
+    check_supported(transport)?;
+    send_bank(transport, bank, bank_desc)
+}
 
-    let mut midi_out = midi_out_connect()?;
-    midi_out.send(&sysex_set_bank(bank, bank_desc))?;
-    midi_out.close();
+/// Reads `bank` back from the device and returns the fields that differ from `bank_desc`.
+///
+/// `bank_desc` is compared as it reads back after encoding, so only differences introduced by the device count.
+/// Notes in the diffs are named in the `middle_c` convention.
+pub fn verify_bank<T: MidiTransport>(
+    transport: &mut T,
+    bank: u8,
+    bank_desc: &MpkBankDescriptor,
+    middle_c: MiddleC,
+) -> Result<Vec<FieldDiff>, AppError> {
+    let expected = MpkBankDescriptor::from(&bank_desc.clone().into_bytes()?)?;
+    let actual = get_bank_desc(transport, bank)?;
+    diff_bank_desc(&expected, &actual, middle_c)
+}
 
+/// Writes `bank_desc` to `bank` and reads it back, failing with [`AppError::VerifyFailed`] on any mismatch.
+pub fn set_bank_verified<T: MidiTransport>(
+    transport: &mut T,
+    bank: u8,
+    bank_desc: MpkBankDescriptor,
+    middle_c: MiddleC,
+) -> Result<(), AppError> {
+    set_bank_from_desc(transport, bank, bank_desc.clone())?;
+    let diffs = verify_bank(transport, bank, &bank_desc, middle_c)?;
+    for d in &diffs {
+        error!("Bank {} mismatch: {} (sent -> device)", bank, d);
+    }
+    if !diffs.is_empty() {
+        return Err(AppError::VerifyFailed(bank, diffs.len()));
+    }
+    info!("Bank {} verified", bank);
     Ok(())
 }
 
-pub fn show_bank(bank: u8) -> Result<(), AppError> {
-    let bank_desc = get_bank_desc(bank)?;
-    println!("Bank {bank}:\n{bank_desc}");
+/// Reads `banks` (0 = RAM) from the device, to be written with note names in the `middle_c` convention.
+pub fn backup<T: MidiTransport>(transport: &mut T, banks: &[u8], middle_c: MiddleC) -> Result<Backup, AppError> {
+    let metadata = BackupMetadata {
+        version: Some(PRESET_VERSION),
+        model: Some(PRESET_MODEL.to_owned()),
+        middle_c: Some(middle_c),
+        tool_version: env!("CARGO_PKG_VERSION").to_owned(),
+        timestamp: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        port: transport.port_name().to_owned(),
+    };
+
+    let mut backup = Backup {
+        metadata,
+        banks: Vec::new(),
+    };
+    for &bank in banks {
+        let settings = get_bank_desc(transport, bank)?;
+        backup.banks.push(BackupBank { bank, settings });
+    }
+    Ok(backup)
+}
+
+/// Writes `banks` (0 = RAM) from `backup` to the device. Nothing is written unless all of them are in the backup.
+pub fn restore<T: MidiTransport>(transport: &mut T, backup: &Backup, banks: &[u8]) -> Result<(), AppError> {
+    for &bank in banks {
+        backup.bank(bank).ok_or(AppError::BackupBankMissing(bank))?;
+        if bank > 4 {
+            return Err(AppError::BankIndexOutOfBounds(bank));
+        }
+    }
+    check_supported(transport)?;
+    for &bank in banks {
+        info!("Restoring bank {}", bank);
+        send_bank(transport, bank, backup.bank(bank).unwrap().clone())?;
+    }
     Ok(())
 }
 
-pub fn dump_bank_yaml(bank: u8) -> Result<(), AppError> {
-    let bank_desc = get_bank_desc(bank)?;
-    let serialized = serde_yaml::to_string(&bank_desc).unwrap();
-    println!("{serialized}");
+/// Copies bank `src` to bank `dst` (0 = RAM) and returns the changes to `dst`. Nothing is written when `dry_run` is set.
+pub fn copy_bank<T: MidiTransport>(
+    transport: &mut T,
+    src: u8,
+    dst: u8,
+    dry_run: bool,
+    middle_c: MiddleC,
+) -> Result<Vec<FieldDiff>, AppError> {
+    let src_desc = get_bank_desc(transport, src)?;
+    let dst_desc = get_bank_desc(transport, dst)?;
+    let diffs = diff_bank_desc(&dst_desc, &src_desc, middle_c)?;
+    if !dry_run {
+        check_supported(transport)?;
+        send_bank(transport, dst, src_desc)?;
+    }
+    Ok(diffs)
+}
+
+/// Swaps banks `a` and `b` (0 = RAM) and returns the changes to `a` and `b`. Nothing is written when `dry_run` is set.
+pub fn swap_banks<T: MidiTransport>(
+    transport: &mut T,
+    a: u8,
+    b: u8,
+    dry_run: bool,
+    middle_c: MiddleC,
+) -> Result<(Vec<FieldDiff>, Vec<FieldDiff>), AppError> {
+    let a_desc = get_bank_desc(transport, a)?;
+    let b_desc = get_bank_desc(transport, b)?;
+    let diffs = (
+        diff_bank_desc(&a_desc, &b_desc, middle_c)?,
+        diff_bank_desc(&b_desc, &a_desc, middle_c)?,
+    );
+    if !dry_run {
+        check_supported(transport)?;
+        send_bank(transport, a, b_desc)?;
+        send_bank(transport, b, a_desc)?;
+    }
+    Ok(diffs)
+}
+
+/// Applies `assignments` to `bank` (0 = RAM) with a read-modify-write and returns the changes.
+///
+/// Note names are in the `middle_c` convention. Nothing is written if any assignment is invalid.
+pub fn set_fields<T: MidiTransport>(
+    transport: &mut T,
+    bank: u8,
+    assignments: &[Assignment],
+    middle_c: MiddleC,
+) -> Result<Vec<FieldDiff>, AppError> {
+    let bank_desc = get_bank_desc(transport, bank)?;
+    let updated = apply_assignments(bank_desc.clone(), assignments, middle_c)?;
+    let diffs = diff_bank_desc(&bank_desc, &updated, middle_c)?;
+    check_supported(transport)?;
+    send_bank(transport, bank, updated)?;
+    Ok(diffs)
+}
+
+pub fn show_bank<T: MidiTransport>(transport: &mut T, bank: u8, middle_c: MiddleC) -> Result<(), AppError> {
+    let bank_desc = get_bank_desc(transport, bank)?;
+    println!("Bank {bank}:\n{}", bank_desc.display_in(middle_c));
+    Ok(())
+}
+
+pub fn dump_bank_yaml<T: MidiTransport>(transport: &mut T, bank: u8, middle_c: MiddleC) -> Result<(), AppError> {
+    let bank_desc = get_bank_desc(transport, bank)?;
+    print!("{}", Preset::new(bank_desc).with_middle_c(middle_c).to_yaml()?);
     Ok(())
 }
+
+/// Writes the bank message as received from the device to standard output, e.g. for a `.syx` file.
+///
+/// With `set_bank`, the message is written as the set bank message writing the same bytes, so that
+/// the file can be replayed on the device.
+pub fn dump_bank_syx<T: MidiTransport>(transport: &mut T, bank: u8, set_bank: bool) -> Result<(), AppError> {
+    let mut bytes = get_bank_sysex(transport, bank)?;
+    if set_bank {
+        bytes = crate::syx::reply_to_set_bank(&bytes)?;
+    }
+    std::io::stdout().write_all(&bytes)?;
+    Ok(())
+}
+
+#[test]
+fn test_get_bank_desc_mock() {
+    use crate::mock::MockMpkMini;
+    use crate::mpkbank::ArpeggiatorMode;
+
+    let mut device = MockMpkMini::new();
+    device
+        .set_bank(
+            3,
+            MpkBankDescriptor::default().with_arpeggiator_mode(ArpeggiatorMode::Random),
+        )
+        .unwrap();
+    device.push_incoming(&[0x90, 0x24, 0x7f]);
+
+    let bank_desc = get_bank_desc(&mut device, 3).unwrap();
+    assert_eq!(ArpeggiatorMode::Random, bank_desc.arpeggiator_mode());
+    let sysex = get_bank_sysex(&mut device, 3).unwrap();
+    assert_eq!(sysex_bank(3, device.bank_bytes(3).unwrap()), sysex);
+    assert!(matches!(
+        get_bank_desc(&mut device, 5),
+        Err(AppError::BankIndexOutOfBounds(5))
+    ));
+}
+
+#[test]
+fn test_set_bank_from_desc_mock() {
+    use crate::mock::MockMpkMini;
+    use crate::mpkbank::Octave;
+
+    let mut device = MockMpkMini::new();
+    let bank_desc = MpkBankDescriptor::default()
+        .with_tempo_taps(4)
+        .with_octave(Octave::new(2).unwrap());
+    set_bank_from_desc(&mut device, 0, bank_desc.clone()).unwrap();
+
+    assert_eq!(
+        bank_desc.into_bytes().unwrap().as_slice(),
+        device.bank_bytes(0).unwrap()
+    );
+    assert_eq!(2, get_bank_desc(&mut device, 0).unwrap().octave().value());
+    assert_eq!(
+        MpkBankDescriptor::default().tempo_taps(),
+        device.bank(1).unwrap().tempo_taps()
+    );
+}
+
+#[test]
+fn test_identify_mock() {
+    use crate::mock::MockMpkMini;
+
+    let mut device = MockMpkMini::new();
+    device.push_incoming(&[0x90, 0x24, 0x7f]);
+    let identity = identify(&mut device).unwrap();
+    assert!(identity.is_supported());
+    assert_eq!(sysex_identity_request(), device.sent()[0]);
+
+    // Devices that do not answer are written to
+    let mut silent = MockMpkMini::new().with_identity(None);
+    assert!(matches!(identify(&mut silent), Err(AppError::MpscRecvTimeoutError(_))));
+    assert!(matches!(
+        show_identity(&mut silent),
+        Err(AppError::DeviceUnidentified(_))
+    ));
+    let bank_desc = MpkBankDescriptor::default().with_tempo_taps(4);
+    set_bank_from_desc(&mut silent, 1, bank_desc.clone()).unwrap();
+    assert_eq!(bank_desc, silent.bank(1).unwrap());
+
+    let mk3 = DeviceIdentity {
+        family: 0x49,
+        ..identity
+    };
+    let mut other = MockMpkMini::new().with_identity(Some(mk3));
+    assert!(matches!(
+        set_bank_from_desc(&mut other, 1, MpkBankDescriptor::default()),
+        Err(AppError::DeviceUnsupported(_))
+    ));
+    assert_eq!(vec![sysex_identity_request()], other.sent());
+    assert!(get_bank_desc(&mut other, 1).is_ok());
+    assert!(matches!(
+        set_fields(&mut other, 1, &["latch=On".parse().unwrap()], MiddleC::C4),
+        Err(AppError::DeviceUnsupported(_))
+    ));
+    assert_eq!(MpkBankDescriptor::default(), other.bank(1).unwrap());
+}
+
+#[test]
+fn test_backup_restore_mock() {
+    use crate::backup::ALL_BANKS;
+    use crate::mock::MockMpkMini;
+    use crate::mpkbank::Transpose;
+
+    let mut device = MockMpkMini::new();
+    device
+        .set_bank(
+            2,
+            MpkBankDescriptor::default().with_transpose(Transpose::new(2).unwrap()),
+        )
+        .unwrap();
+    let backup = backup(&mut device, &ALL_BANKS, MiddleC::C4).unwrap();
+    assert_eq!(5, backup.banks.len());
+    assert_eq!("MockMpkMini", backup.metadata.port);
+
+    let mut other = MockMpkMini::new();
+    assert!(matches!(
+        restore(&mut other, &backup, &[5]),
+        Err(AppError::BackupBankMissing(5))
+    ));
+    assert!(other.sent().is_empty());
+
+    restore(&mut other, &backup, &[2]).unwrap();
+    assert_eq!(2, other.bank(2).unwrap().transpose().value());
+}
+
+#[test]
+fn test_backup_restore_device_values_mock() {
+    use crate::mock::MockMpkMini;
+    use crate::mpkbank::Knob;
+
+    // Values outside the documented ranges, as the device may report them
+    let mut device = MockMpkMini::new();
+    let bank_desc = MpkBankDescriptor::default()
+        .with_tempo_taps(9)
+        .with_knob(0, Knob::new(1, 100, 10))
+        .unwrap();
+    device.set_bank(1, bank_desc).unwrap();
+    let bytes = device.bank_bytes(1).unwrap().to_vec();
+
+    let yaml = backup(&mut device, &[1], MiddleC::C4).unwrap().to_yaml().unwrap();
+    let mut other = MockMpkMini::new();
+    restore(&mut other, &Backup::from_yaml(yaml.as_bytes()).unwrap(), &[1]).unwrap();
+    assert_eq!(bytes, other.bank_bytes(1).unwrap());
+}
+
+#[test]
+fn test_set_bank_verified_mock() {
+    use crate::mock::MockMpkMini;
+
+    let mut device = MockMpkMini::new();
+    let bank_desc = MpkBankDescriptor::default().with_latch(crate::mpkbank::Toggle::On);
+    set_bank_verified(&mut device, 1, bank_desc.clone(), MiddleC::C4).unwrap();
+
+    // A device that ignores writes
+    let mut device = MockMpkMini::new();
+    let diffs = verify_bank(&mut device, 1, &bank_desc, MiddleC::C4).unwrap();
+    assert_eq!(1, diffs.len());
+    assert_eq!("latch", diffs[0].path);
+}
+
+#[test]
+fn test_copy_swap_banks_mock() {
+    use crate::mock::MockMpkMini;
+    use crate::mpkbank::Octave;
+
+    let mut device = MockMpkMini::new();
+    device
+        .set_bank(1, MpkBankDescriptor::default().with_octave(Octave::new(1).unwrap()))
+        .unwrap();
+    device
+        .set_bank(2, MpkBankDescriptor::default().with_octave(Octave::new(2).unwrap()))
+        .unwrap();
+
+    let diffs = copy_bank(&mut device, 1, 0, true, MiddleC::C4).unwrap();
+    assert_eq!(1, diffs.len());
+    assert_eq!(0, device.bank(0).unwrap().octave().value());
+
+    copy_bank(&mut device, 1, 0, false, MiddleC::C4).unwrap();
+    assert_eq!(1, device.bank(0).unwrap().octave().value());
+
+    swap_banks(&mut device, 1, 2, false, MiddleC::C4).unwrap();
+    assert_eq!(2, device.bank(1).unwrap().octave().value());
+    assert_eq!(1, device.bank(2).unwrap().octave().value());
+
+    // Values outside the documented ranges are copied as read from the device
+    let mut bank_bytes = device.bank_bytes(3).unwrap().to_vec();
+    bank_bytes[9] = 9; // tempo taps
+    bank_bytes[84] = 100; // knob 0 min
+    bank_bytes[85] = 10; // knob 0 max
+    device
+        .set_bank(3, MpkBankDescriptor::from(&bank_bytes).unwrap())
+        .unwrap();
+    copy_bank(&mut device, 3, 4, false, MiddleC::C4).unwrap();
+    assert_eq!(bank_bytes, device.bank_bytes(4).unwrap());
+    swap_banks(&mut device, 3, 1, false, MiddleC::C4).unwrap();
+    assert_eq!(bank_bytes, device.bank_bytes(1).unwrap());
+}
+
+#[test]
+fn test_set_fields_mock() {
+    use crate::mock::MockMpkMini;
+
+    let mut device = MockMpkMini::new();
+    let assignments = ["knobs[1].max=100".parse().unwrap(), "latch=On".parse().unwrap()];
+    let diffs = set_fields(&mut device, 4, &assignments, MiddleC::C4).unwrap();
+    assert_eq!(2, diffs.len());
+    assert_eq!(100, device.bank(4).unwrap().knobs()[1].max());
+
+    let sent = device.sent().len();
+    assert!(set_fields(&mut device, 4, &["latch=Maybe".parse().unwrap()], MiddleC::C4).is_err());
+    assert_eq!(sent + 1, device.sent().len());
+}
+
+#[test]
+fn test_passthrough_mock() {
+    use crate::mock::MockMpkMini;
+
+    let mut device = MockMpkMini::new();
+    device.push_incoming(&[0x90, 0x24, 0x7f]);
+    device.push_incoming(&[0x80, 0x24, 0x00]);
+    device.disconnect();
+
+    assert!(passthrough(&mut device).is_err());
+    assert_eq!(&[vec![0x90, 0x24, 0x7f], vec![0x80, 0x24, 0x00]], device.sent());
+}
@@ -0,0 +1,184 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+use std::io::Read;
+
+use serde_yaml::Value;
+
+use crate::error::AppError;
+use crate::mpkbank::{MiddleC, MpkBankDescriptor, MpkBankDescriptorFile};
+use crate::preset::{detect_version, middle_c, migrate};
+use crate::validate::validate_fields;
+
+/// Partial bank descriptor containing only some fields, e.g. just `pads`.
+///
+/// Mappings are merged recursively. A sequence replaces the leading elements of the base sequence
+/// (e.g. a list of 4 knobs patches knobs 1-4), and a mapping with integer keys patches single elements
+/// (e.g. `knobs: {5: {control: 74}}` patches the sixth knob).
+///
+/// Versioned presets (see [`crate::preset`]) are migrated and their settings used as the patch, with note
+/// names in the preset's octave convention; bare patches use the convention given when reading them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BankPatch {
+    fields: Value,
+    middle_c: MiddleC,
+}
+
+fn field_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+// Merges `patch` into `base`, collecting the paths of the patched fields in `patched`
+fn merge_values(path: &str, base: &mut Value, patch: &Value, patched: &mut Vec<String>) -> Result<(), AppError> {
+    match (base, patch) {
+        (Value::Mapping(base), Value::Mapping(patch)) => {
+            for (key, value) in patch {
+                let child = field_path(path, key.as_str().unwrap_or_default());
+                match base.get_mut(key) {
+                    Some(base) => merge_values(&child, base, value, patched)?,
+                    None => return Err(AppError::PatchUnknownField(child)),
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(patch)) => {
+            if patch.len() > base.len() {
+                return Err(AppError::PatchInvalid(format!(
+                    "{} elements for {path} (expected at most {})",
+                    patch.len(),
+                    base.len()
+                )));
+            }
+            for (i, value) in patch.iter().enumerate() {
+                merge_values(&format!("{path}[{i}]"), &mut base[i], value, patched)?;
+            }
+        }
+        (Value::Sequence(base), Value::Mapping(patch)) => {
+            for (key, value) in patch {
+                let index = key
+                    .as_u64()
+                    .and_then(|i| usize::try_from(i).ok())
+                    .filter(|&i| i < base.len())
+                    .ok_or_else(|| AppError::PatchInvalid(format!("invalid index {key:?} for {path}")))?;
+                merge_values(&format!("{path}[{index}]"), &mut base[index], value, patched)?;
+            }
+        }
+        (base, patch) => {
+            *base = patch.clone();
+            patched.push(path.to_owned());
+        }
+    }
+    Ok(())
+}
+
+impl BankPatch {
+    /// Reads a patch, with note names of bare patches in the `middle_c` convention.
+    pub fn from_reader<R: Read>(reader: R, middle_c: MiddleC) -> Result<Self, AppError> {
+        let mut value: Value = serde_yaml::from_reader(reader)?;
+        let mut middle_c = middle_c;
+        if detect_version(&value)? > 1 {
+            let preset = migrate(value)?;
+            middle_c = self::middle_c(&preset)?;
+            value = preset
+                .get("settings")
+                .cloned()
+                .ok_or_else(|| AppError::PatchInvalid("preset without settings".to_owned()))?;
+        }
+        let fields = match value {
+            Value::Mapping(_) => value,
+            Value::Null => Value::Mapping(Default::default()),
+            _ => return Err(AppError::PatchInvalid("expected a mapping of fields".to_owned())),
+        };
+        Ok(BankPatch { fields, middle_c })
+    }
+
+    /// Returns `base` with the fields of this patch applied.
+    ///
+    /// Only the patched fields are validated; other settings of `base` are kept as they are.
+    pub fn apply(&self, base: &MpkBankDescriptor) -> Result<MpkBankDescriptor, AppError> {
+        let mut value = serde_yaml::to_value(MpkBankDescriptorFile::new(base, self.middle_c))?;
+        let mut patched = Vec::new();
+        merge_values("", &mut value, &self.fields, &mut patched)?;
+        let bank_desc = serde_yaml::from_value::<MpkBankDescriptorFile>(value)?.into_bank_desc(self.middle_c)?;
+        validate_fields(&bank_desc, &patched)?;
+        Ok(bank_desc)
+    }
+}
+
+/// Applies `patches` in order.
+pub fn apply_patches(base: MpkBankDescriptor, patches: &[BankPatch]) -> Result<MpkBankDescriptor, AppError> {
+    patches
+        .iter()
+        .try_fold(base, |bank_desc, patch| patch.apply(&bank_desc))
+}
+
+#[test]
+fn test_bank_patch() {
+    let patch = |s: &str| BankPatch::from_reader(s.as_bytes(), MiddleC::C4).unwrap();
+
+    let knobs = patch("knobs:\n- control: 20\n- {control: 21, max: 100}\n");
+    let pads = patch("pads:\n  15: {note: C 2, mode: Toggle}\narpeggiator_mode: Random\n");
+    let bank_desc = apply_patches(MpkBankDescriptor::default(), &[knobs, pads]).unwrap();
+
+    assert_eq!(20, bank_desc.knobs()[0].control());
+    assert_eq!((21, 0, 100), {
+        let k = bank_desc.knobs()[1];
+        (k.control(), k.min(), k.max())
+    });
+    assert_eq!(MpkBankDescriptor::default().knobs()[2], bank_desc.knobs()[2]);
+    assert_eq!(36, bank_desc.pads()[15].note().value());
+    assert_eq!(crate::mpkbank::ArpeggiatorMode::Random, bank_desc.arpeggiator_mode());
+
+    let base = MpkBankDescriptor::default();
+    assert!(matches!(
+        patch("knobs:\n- {contrl: 1}\n").apply(&base),
+        Err(AppError::PatchUnknownField(f)) if f == "knobs[0].contrl"
+    ));
+    assert!(patch("pads:\n  16: {control: 1}\n").apply(&base).is_err());
+    assert!(patch("octave: high\n").apply(&base).is_err());
+
+    let preset = patch("version: 3\nmodel: MPK Mini mkII\nsettings:\n  octave: -2\n");
+    assert_eq!(-2, preset.apply(&base).unwrap().octave().value());
+    let preset = patch("version: 4\nmodel: MPK Mini mkII\nmiddle_c: C3\nsettings:\n  pads: {0: {note: C 3}}\n");
+    assert_eq!(60, preset.apply(&base).unwrap().pads()[0].note().value());
+    let bare = BankPatch::from_reader("pads: {0: {note: C 3}}\n".as_bytes(), MiddleC::C3).unwrap();
+    assert_eq!(60, bare.apply(&base).unwrap().pads()[0].note().value());
+
+    // Only patched fields are validated, base settings read from the device are kept
+    let device = base.clone().with_tempo_taps(9);
+    let bank_desc = patch("knobs: {0: {max: 100}}\n").apply(&device).unwrap();
+    assert_eq!(9, bank_desc.tempo_taps());
+    assert!(matches!(
+        patch("tempo_taps: 9\n").apply(&base),
+        Err(AppError::ValidationFailed(p)) if p.len() == 1 && p[0].path == "tempo_taps"
+    ));
+    assert!(matches!(
+        patch("knobs: {0: {min: 100, max: 10}}\n").apply(&device),
+        Err(AppError::ValidationFailed(p)) if p.len() == 1 && p[0].path == "knobs[0]"
+    ));
+}
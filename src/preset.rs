@@ -0,0 +1,378 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+//! Versioned preset files.
+//!
+//! A preset wraps the settings of one bank in an envelope identifying the schema version and
+//! the device model:
+//!
+//! ```yaml
+//! version: 4
+//! model: MPK Mini mkII
+//! middle_c: C4
+//! settings:
+//!   octave: 0
+//!   ...
+//! ```
+//!
+//! Older files are migrated to the latest version when read:
+//! * version 1: bare settings with raw device values (e.g. MIDI channels 0..15)
+//! * version 2: bare settings in human units, marked with `format_version: 2`
+//! * version 3: settings in an envelope, with note names in the `C4` convention
+//!
+//! Note names are read and written in the octave convention recorded in `middle_c`.
+
+use std::io::Read;
+
+use schemars::{JsonSchema, Schema};
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use crate::bankfile::preset_file_format;
+use crate::error::AppError;
+use crate::mpkbank::{MiddleC, MpkBankDescriptor, MpkBankDescriptorFile};
+
+/// Latest preset schema version.
+pub const PRESET_VERSION: u32 = 4;
+
+/// Device model recorded in presets.
+pub const PRESET_MODEL: &str = "MPK Mini mkII";
+
+/// Text formats presets are stored in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PresetFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+/// Settings of one bank with schema version, device model and note octave convention.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(try_from = "PresetFile", into = "PresetFile")]
+pub struct Preset {
+    pub version: u32,
+    pub model: String,
+    pub middle_c: MiddleC,
+    pub settings: MpkBankDescriptor,
+}
+
+// Serialized form, with note names in the `middle_c` convention
+/// Settings of one bank with schema version, device model and note octave convention.
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(rename = "Preset")]
+struct PresetFile {
+    #[schemars(extend("const" = PRESET_VERSION))]
+    version: u32,
+    #[schemars(extend("const" = PRESET_MODEL))]
+    model: String,
+    middle_c: MiddleC,
+    settings: MpkBankDescriptorFile,
+}
+
+impl TryFrom<PresetFile> for Preset {
+    type Error = AppError;
+
+    fn try_from(file: PresetFile) -> Result<Self, AppError> {
+        Ok(Preset {
+            version: file.version,
+            model: file.model,
+            middle_c: file.middle_c,
+            settings: file.settings.into_bank_desc(file.middle_c)?,
+        })
+    }
+}
+
+impl From<Preset> for PresetFile {
+    fn from(preset: Preset) -> Self {
+        PresetFile {
+            version: preset.version,
+            model: preset.model,
+            middle_c: preset.middle_c,
+            settings: MpkBankDescriptorFile::new(&preset.settings, preset.middle_c),
+        }
+    }
+}
+
+// Upgrades a preset from version N (index N - 1) to N + 1
+type Migration = fn(Value) -> Result<Value, AppError>;
+
+const MIGRATIONS: [Migration; PRESET_VERSION as usize - 1] = [migrate_v1_to_v2, migrate_v2_to_v3, migrate_v3_to_v4];
+
+// Fields stored with a device offset in version 1, and the offset to convert to human units
+const V1_OFFSET_FIELDS: [(&str, i64); 5] = [
+    ("octave", -4),
+    ("transpose", -12),
+    ("pad_midi_channel", 1),
+    ("keybed_channel", 1),
+    ("arpeggiator_octave", 1),
+];
+
+fn as_mapping(value: &mut Value) -> Result<&mut Mapping, AppError> {
+    value
+        .as_mapping_mut()
+        .ok_or_else(|| AppError::PresetInvalid("expected a mapping".to_owned()))
+}
+
+fn migrate_v1_to_v2(mut value: Value) -> Result<Value, AppError> {
+    let fields = as_mapping(&mut value)?;
+    for (field, offset) in V1_OFFSET_FIELDS {
+        if let Some(v) = fields.get_mut(field) {
+            let human = v
+                .as_i64()
+                .and_then(|raw| raw.checked_add(offset))
+                .ok_or_else(|| AppError::PresetInvalid(format!("{field} is not a valid number")))?;
+            *v = Value::from(human);
+        }
+    }
+    fields.insert(Value::from("format_version"), Value::from(2));
+    Ok(value)
+}
+
+fn migrate_v2_to_v3(mut value: Value) -> Result<Value, AppError> {
+    as_mapping(&mut value)?.remove("format_version");
+    let mut preset = Mapping::new();
+    preset.insert(Value::from("version"), Value::from(3));
+    preset.insert(Value::from("model"), Value::from(PRESET_MODEL));
+    preset.insert(Value::from("settings"), value);
+    Ok(Value::Mapping(preset))
+}
+
+fn migrate_v3_to_v4(mut value: Value) -> Result<Value, AppError> {
+    let preset = as_mapping(&mut value)?;
+    preset.insert(Value::from("version"), Value::from(4));
+    // Settings are the last field in the written file
+    let settings = preset.remove("settings");
+    preset.insert(Value::from("middle_c"), Value::from(MiddleC::C4.to_string()));
+    if let Some(settings) = settings {
+        preset.insert(Value::from("settings"), settings);
+    }
+    Ok(value)
+}
+
+/// Returns the note octave convention of a preset migrated to the latest version.
+pub fn middle_c(value: &Value) -> Result<MiddleC, AppError> {
+    match value.get("middle_c") {
+        Some(middle_c) => Ok(serde_yaml::from_value(middle_c.clone())?),
+        None => Err(AppError::PresetInvalid("missing middle_c".to_owned())),
+    }
+}
+
+/// Returns the schema version of a preset: `version` for enveloped presets, otherwise
+/// `format_version` (2) or 1 for bare settings.
+pub fn detect_version(value: &Value) -> Result<u32, AppError> {
+    let version = match value.get("version").or_else(|| value.get("format_version")) {
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| AppError::PresetInvalid(format!("invalid version {version:?}")))?,
+        None => 1,
+    };
+    match u32::try_from(version) {
+        Ok(version) if (1..=PRESET_VERSION).contains(&version) => Ok(version),
+        _ => Err(AppError::PresetVersionUnsupported(version)),
+    }
+}
+
+/// Parses a preset in `format` without migrating it, e.g. to detect its version.
+pub fn parse_value(s: &str, format: PresetFormat) -> Result<Value, AppError> {
+    Ok(match format {
+        PresetFormat::Yaml => serde_yaml::from_str(s)?,
+        PresetFormat::Json => serde_json::from_str(s)?,
+        PresetFormat::Toml => toml::from_str(s)?,
+    })
+}
+
+// Deserializes (part of) a migrated preset parsed from `format`
+pub(crate) fn from_migrated<T: DeserializeOwned>(value: Value, format: PresetFormat) -> Result<T, AppError> {
+    match format {
+        PresetFormat::Yaml => Ok(serde_yaml::from_value(value)?),
+        // Enums are maps ({ControlChannel: 1}) rather than yaml tags here, which only
+        // serde_json::Value deserializes into enums
+        PresetFormat::Json | PresetFormat::Toml => Ok(serde_json::from_value(serde_json::to_value(value)?)?),
+    }
+}
+
+/// Migrates a preset of any supported version to the latest version.
+pub fn migrate(value: Value) -> Result<Value, AppError> {
+    let version = detect_version(&value)?;
+    MIGRATIONS[version as usize - 1..]
+        .iter()
+        .try_fold(value, |value, migration| migration(value))
+}
+
+/// Rewrites a preset file of an older version in the latest version, in the format of its extension.
+///
+/// Returns the version the file was migrated from, or `None` if it already is at the latest version.
+pub fn migrate_file(filename: &str) -> Result<Option<u32>, AppError> {
+    let format = preset_file_format(filename)?;
+    let s = std::fs::read_to_string(filename)?;
+    let version = detect_version(&parse_value(&s, format)?)?;
+    if version == PRESET_VERSION {
+        return Ok(None);
+    }
+    std::fs::write(filename, Preset::from_str_as(&s, format)?.to_string_as(format)?)?;
+    Ok(Some(version))
+}
+
+impl Preset {
+    /// Preset of the latest version, with note names in the default `C4` convention.
+    pub fn new(settings: MpkBankDescriptor) -> Self {
+        Preset {
+            version: PRESET_VERSION,
+            model: PRESET_MODEL.to_owned(),
+            middle_c: MiddleC::default(),
+            settings,
+        }
+    }
+
+    pub fn with_middle_c(mut self, middle_c: MiddleC) -> Self {
+        self.middle_c = middle_c;
+        self
+    }
+
+    fn check_model(self) -> Result<Self, AppError> {
+        match self.model == PRESET_MODEL {
+            true => Ok(self),
+            false => Err(AppError::PresetModelUnsupported(self.model)),
+        }
+    }
+
+    /// Migrates `value` to the latest version and checks the device model.
+    pub fn from_value(value: Value) -> Result<Self, AppError> {
+        serde_yaml::from_value::<Preset>(migrate(value)?)?.check_model()
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, AppError> {
+        Self::from_value(serde_yaml::from_reader(reader)?)
+    }
+
+    pub fn to_yaml(&self) -> Result<String, AppError> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    /// Parses a preset in `format`, migrating it to the latest version.
+    pub fn from_str_as(s: &str, format: PresetFormat) -> Result<Self, AppError> {
+        from_migrated::<Preset>(migrate(parse_value(s, format)?)?, format)?.check_model()
+    }
+
+    /// JSON Schema of the latest preset version, with value ranges and enum spellings.
+    pub fn json_schema() -> Schema {
+        schemars::schema_for!(Preset)
+    }
+
+    pub fn to_string_as(&self, format: PresetFormat) -> Result<String, AppError> {
+        Ok(match format {
+            PresetFormat::Yaml => self.to_yaml()?,
+            PresetFormat::Json => serde_json::to_string_pretty(self)? + "\n",
+            PresetFormat::Toml => toml::to_string(self)?,
+        })
+    }
+}
+
+#[test]
+fn test_preset_migration() {
+    use crate::mpkbank::{Joystick, Note, Octave, Pad, PadMode};
+
+    let settings = MpkBankDescriptor::default().with_octave(Octave::new(-1).unwrap());
+    let yaml = Preset::new(settings.clone()).to_yaml().unwrap();
+    assert!(yaml.starts_with("version: 4\nmodel: MPK Mini mkII\nmiddle_c: C4\nsettings:\n  octave: -1\n"));
+    assert_eq!(settings, Preset::from_reader(yaml.as_bytes()).unwrap().settings);
+
+    let v2 = format!("format_version: 2\n{}", serde_yaml::to_string(&settings).unwrap());
+    let v1 = serde_yaml::to_string(&settings)
+        .unwrap()
+        .replace("octave: -1\n", "octave: 3\n")
+        .replace("transpose: 0\n", "transpose: 12\n")
+        .replace("pad_midi_channel: 10\n", "pad_midi_channel: 9\n")
+        .replace("keybed_channel: 1\n", "keybed_channel: 0\n")
+        .replace("arpeggiator_octave: 1\n", "arpeggiator_octave: 0\n");
+    let v3 = yaml.replace("version: 4", "version: 3").replace("middle_c: C4\n", "");
+    for (version, yaml) in [(3, v3), (2, v2), (1, v1)] {
+        let value: Value = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(version, detect_version(&value).unwrap());
+        assert_eq!(settings, Preset::from_value(value).unwrap().settings);
+    }
+
+    for format in [PresetFormat::Yaml, PresetFormat::Json, PresetFormat::Toml] {
+        let preset = Preset::new(settings.clone().with_joystick_y(Joystick::SplitControlChannels(3, 4)));
+        let serialized = preset.to_string_as(format).unwrap();
+        assert_eq!(preset, Preset::from_str_as(&serialized, format).unwrap());
+    }
+
+    // Notes are read and written in the recorded convention
+    let settings = settings
+        .with_pad(0, Pad::new(Note::new(60), 1, 1, PadMode::Momentary))
+        .unwrap();
+    let c3 = Preset::new(settings.clone()).with_middle_c(MiddleC::C3);
+    assert_eq!(MiddleC::C3, c3.middle_c);
+    for format in [PresetFormat::Yaml, PresetFormat::Json, PresetFormat::Toml] {
+        let serialized = c3.to_string_as(format).unwrap();
+        assert!(serialized.contains("C 3"), "{serialized}");
+        assert_eq!(c3, Preset::from_str_as(&serialized, format).unwrap());
+    }
+    assert!(Preset::new(settings).to_yaml().unwrap().contains("note: C 4\n"));
+
+    // Settings read from the device are read back as they are, even outside the documented ranges
+    let device = Preset::new(MpkBankDescriptor::default().with_tempo_taps(9));
+    assert_eq!(
+        device,
+        Preset::from_reader(device.to_yaml().unwrap().as_bytes()).unwrap()
+    );
+
+    let future = yaml.replace("version: 4", "version: 99");
+    assert!(matches!(
+        Preset::from_reader(future.as_bytes()),
+        Err(AppError::PresetVersionUnsupported(99))
+    ));
+    let other = yaml.replace("MPK Mini mkII", "MPK Mini mk3");
+    assert!(matches!(
+        Preset::from_reader(other.as_bytes()),
+        Err(AppError::PresetModelUnsupported(_))
+    ));
+}
+
+#[test]
+fn test_preset_json_schema() {
+    let schema = Preset::json_schema();
+    let settings = schema.pointer("/$defs/MpkBankDescriptor/properties").unwrap();
+    assert_eq!(-4, settings["octave"]["minimum"]);
+    assert_eq!(240, settings["tempo"]["maximum"]);
+    assert_eq!(8, settings["knobs"]["minItems"]);
+    assert_eq!(
+        127,
+        schema
+            .pointer("/$defs/Knob/properties/control/maximum")
+            .unwrap()
+            .as_u64()
+            .unwrap()
+    );
+    assert!(schema.pointer("/$defs/Pad/properties/note/anyOf/0/pattern").is_some());
+    assert!(schema
+        .pointer("/$defs/Swing/enum")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .contains(&"_57".into()));
+}
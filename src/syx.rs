@@ -0,0 +1,177 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+//! Raw sysex files (`.syx`), as recorded and replayed by tools like `amidi`.
+//!
+//! A file holds one or more complete bank messages (`F0 47 ... F7`): either the device's bank replies
+//! (`00 26 67` header) exactly as sent by the device, or the set bank messages (`00 26 64` header) writing the
+//! same settings, which the device applies when the file is replayed (e.g. `amidi -s`).
+
+use std::io::Read;
+
+use crate::error::AppError;
+use crate::mpkbank::MpkBankDescriptor;
+use crate::mpkmidi::{
+    sysex_bank, sysex_set_bank, MpkMidiMessage, SysExId, MIDI_RESET, MIDI_SYSEX, MIDI_SYSEX_END, MIDI_TIMING_CLOCK,
+    SYSEX_AKAI, SYSEX_MPK_BANK, SYSEX_MPK_SET_BANK,
+};
+
+/// Splits `bytes` into complete sysex messages.
+///
+/// Real time bytes (e.g. timing clocks interleaved in `amidi` captures) are skipped, also within a message.
+pub fn split_sysex(bytes: &[u8]) -> Result<Vec<Vec<u8>>, AppError> {
+    let mut messages = Vec::new();
+    let mut pending: Option<Vec<u8>> = None;
+    for (offset, &byte) in bytes.iter().enumerate() {
+        match (byte, pending.as_mut()) {
+            (MIDI_TIMING_CLOCK..=MIDI_RESET, _) => {}
+            (MIDI_SYSEX, None) => pending = Some(vec![byte]),
+            (MIDI_SYSEX_END, Some(msg)) => {
+                msg.push(byte);
+                messages.extend(pending.take());
+            }
+            (_, Some(msg)) => msg.push(byte),
+            (_, None) => {
+                return Err(AppError::SyxInvalid(format!(
+                    "unexpected byte {byte:#04x} at offset {offset}"
+                )))
+            }
+        }
+    }
+    match pending {
+        Some(_) => Err(AppError::SyxInvalid("unterminated sysex message".to_owned())),
+        None => Ok(messages),
+    }
+}
+
+// Bank replies and set bank messages, as (bank, settings)
+fn parse_bank_msg(msg: &[u8]) -> Result<(u8, MpkBankDescriptor), AppError> {
+    match MpkMidiMessage::parse_msg(msg)? {
+        MpkMidiMessage::Bank(bank, bank_desc) => Ok((bank, bank_desc)),
+        MpkMidiMessage::SysEx(SysExId::Manufacturer(SYSEX_AKAI), data) if data.starts_with(&SYSEX_MPK_SET_BANK) => {
+            match data[SYSEX_MPK_SET_BANK.len()..].split_first() {
+                Some((&bank, bank_bytes)) => Ok((bank, MpkBankDescriptor::from(bank_bytes)?)),
+                None => Err(AppError::SyxInvalid("truncated set bank message".to_owned())),
+            }
+        }
+        other => Err(AppError::SyxInvalid(format!("not a bank message: {other:?}"))),
+    }
+}
+
+/// Decodes the bank messages (bank replies or set bank messages) of a `.syx` file, as (bank, settings)
+/// in file order.
+pub fn from_syx(bytes: &[u8]) -> Result<Vec<(u8, MpkBankDescriptor)>, AppError> {
+    let banks = split_sysex(bytes)?
+        .into_iter()
+        .map(|msg| parse_bank_msg(&msg))
+        .collect::<Result<Vec<_>, _>>()?;
+    if banks.is_empty() {
+        return Err(AppError::SyxInvalid("no bank messages".to_owned()));
+    }
+    Ok(banks)
+}
+
+pub fn read_syx<R: Read>(mut reader: R) -> Result<Vec<(u8, MpkBankDescriptor)>, AppError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    from_syx(&bytes)
+}
+
+/// Encodes `bank_desc` as the message the device sends for `bank` (0 = RAM).
+pub fn to_syx(bank: u8, bank_desc: MpkBankDescriptor) -> Result<Vec<u8>, AppError> {
+    Ok(sysex_bank(bank, &bank_desc.into_bytes()?))
+}
+
+/// Encodes `bank_desc` as the set bank message writing it to `bank` (0 = RAM), to replay on the device.
+pub fn to_syx_set_bank(bank: u8, bank_desc: MpkBankDescriptor) -> Result<Vec<u8>, AppError> {
+    sysex_set_bank(bank, bank_desc)
+}
+
+/// Turns a bank message as sent by the device into the set bank message writing the same bytes back.
+pub fn reply_to_set_bank(msg: &[u8]) -> Result<Vec<u8>, AppError> {
+    let header = 2..2 + SYSEX_MPK_BANK.len();
+    if msg.get(..2) != Some(&[MIDI_SYSEX, SYSEX_AKAI]) || msg.get(header.clone()) != Some(&SYSEX_MPK_BANK) {
+        return Err(AppError::SyxInvalid("not a bank message".to_owned()));
+    }
+    let mut set_bank = msg.to_vec();
+    set_bank[header].copy_from_slice(&SYSEX_MPK_SET_BANK);
+    Ok(set_bank)
+}
+
+#[test]
+fn test_syx() {
+    use crate::mpkbank::Octave;
+
+    let bank1 = MpkBankDescriptor::default();
+    let bank3 = MpkBankDescriptor::default().with_octave(Octave::new(-3).unwrap());
+    let mut bytes = to_syx(1, bank1).unwrap();
+    bytes.extend(to_syx(3, bank3).unwrap());
+    assert_eq!(2 * 117, bytes.len());
+
+    let banks = read_syx(bytes.as_slice()).unwrap();
+    assert_eq!(vec![1, 3], banks.iter().map(|(bank, _)| *bank).collect::<Vec<_>>());
+    assert_eq!(-3, banks[1].1.octave().value());
+
+    assert!(matches!(from_syx(&bytes[..200]), Err(AppError::SyxInvalid(_))));
+    assert!(matches!(from_syx(&bytes[1..]), Err(AppError::SyxInvalid(_))));
+
+    // Timing clocks interleaved by amidi captures, between and within messages
+    let mut captured = vec![0xf8];
+    captured.extend(&bytes[..117]);
+    captured.insert(20, 0xf8);
+    captured.extend([0xf8, 0xfe]);
+    captured.extend(&bytes[117..]);
+    assert_eq!(split_sysex(&bytes).unwrap(), split_sysex(&captured).unwrap());
+    assert_eq!(banks, from_syx(&captured).unwrap());
+    assert!(matches!(from_syx(&[]), Err(AppError::SyxInvalid(_))));
+    assert!(matches!(
+        from_syx(&crate::mpkmidi::sysex_get_bank(1)),
+        Err(AppError::SyxInvalid(_))
+    ));
+}
+
+#[test]
+fn test_syx_set_bank() {
+    use crate::mpkbank::Octave;
+
+    let bank2 = MpkBankDescriptor::default().with_octave(Octave::new(2).unwrap());
+    let reply = to_syx(2, bank2.clone()).unwrap();
+    let set_bank = reply_to_set_bank(&reply).unwrap();
+    assert_eq!(to_syx_set_bank(2, bank2.clone()).unwrap(), set_bank);
+    assert_eq!([0xf0, 0x47, 0x00, 0x26, 0x64, 0x00, 0x6d, 0x02], set_bank[..8]);
+    assert_eq!(reply[8..], set_bank[8..]);
+
+    // Both forms are read back, also mixed in one file
+    let mut bytes = set_bank;
+    bytes.extend(to_syx(3, MpkBankDescriptor::default()).unwrap());
+    let banks = from_syx(&bytes).unwrap();
+    assert_eq!(vec![(2, bank2), (3, MpkBankDescriptor::default())], banks);
+
+    assert!(matches!(reply_to_set_bank(&bytes[..117]), Err(AppError::SyxInvalid(_))));
+    assert!(matches!(
+        from_syx(&[0xf0, 0x47, 0x00, 0x26, 0x64, 0x00, 0x6d, 0xf7]),
+        Err(AppError::SyxInvalid(_))
+    ));
+}
@@ -0,0 +1,96 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use log::{debug, error};
+use midir::{MidiInputConnection, MidiOutputConnection};
+
+use crate::error::AppError;
+use crate::util::*;
+
+/// Bidirectional connection to an MPK Mini (or anything pretending to be one).
+pub trait MidiTransport {
+    /// Sends a single complete MIDI message.
+    fn send(&mut self, bytes: &[u8]) -> Result<(), AppError>;
+
+    /// Waits up to `timeout` for the next received MIDI message.
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<Vec<u8>, AppError>;
+
+    /// Name of the port (or device) messages are sent to.
+    fn port_name(&self) -> &str;
+}
+
+/// Transport over the MIDI ports of a connected device.
+pub struct MidirTransport {
+    midi_out: MidiOutputConnection,
+    midi_in: MidiInputConnection<()>,
+    rx: mpsc::Receiver<Vec<u8>>,
+    port_name: String,
+}
+
+impl MidirTransport {
+    pub fn connect(in_port: &PortSelector, out_port: &PortSelector) -> Result<Self, AppError> {
+        let (tx, rx) = mpsc::channel();
+
+        let cb = move |_, bytes: &[u8], _: &mut _| {
+            debug!("rx bytes: {:?}", bytes);
+            if let Err(e) = tx.send(Vec::from(bytes)) {
+                error!("Error while sending on channel: {}", e);
+            }
+        };
+
+        let (midi_out, port_name) = midi_out_connect(out_port)?;
+        let (midi_in, in_port_name) = midi_in_connect(in_port, cb, ())?;
+        debug!("Connected to output '{}', input '{}'", port_name, in_port_name);
+
+        Ok(MidirTransport {
+            midi_out,
+            midi_in,
+            rx,
+            port_name,
+        })
+    }
+
+    pub fn close(self) {
+        self.midi_out.close();
+        self.midi_in.close();
+    }
+}
+
+impl MidiTransport for MidirTransport {
+    fn send(&mut self, bytes: &[u8]) -> Result<(), AppError> {
+        Ok(self.midi_out.send(bytes)?)
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<Vec<u8>, AppError> {
+        Ok(self.rx.recv_timeout(timeout)?)
+    }
+
+    fn port_name(&self) -> &str {
+        &self.port_name
+    }
+}
@@ -23,19 +23,80 @@
  *
  */
 
+use std::borrow::Cow;
 use std::fmt;
 
-use serde_derive::{Deserialize, Serialize};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_derive::Deserialize;
 
 use crate::error::AppError;
 
 /* 14 bits unsigned, big endian */
-#[derive(Serialize, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub struct U14BE {
     host: u16,
 }
 
+// Plain number, or the nested form ({host: 120}) written by earlier versions
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum U14BEFile {
+    Value(u16),
+    Nested { host: u16 },
+}
+
+impl JsonSchema for U14BE {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        "U14BE".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "integer",
+            "minimum": 0,
+            "maximum": 0x3fff,
+        })
+    }
+}
+
+impl Serialize for U14BE {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u16(self.host)
+    }
+}
+
+impl<'de> Deserialize<'de> for U14BE {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (U14BEFile::Value(value) | U14BEFile::Nested { host: value }) = U14BEFile::deserialize(deserializer)?;
+        U14BE::new(value).map_err(de::Error::custom)
+    }
+}
+
 impl U14BE {
+    pub fn new(value: u16) -> Result<U14BE, AppError> {
+        if value & 0xc000 != 0 {
+            Err(AppError::U14BEValueTooLarge(value))
+        } else {
+            Ok(U14BE { host: value })
+        }
+    }
+
+    pub fn value(&self) -> u16 {
+        self.host
+    }
+
     pub fn from_device(bytes: [u8; 2]) -> Result<U14BE, AppError> {
         if ((bytes[0] | bytes[1]) & 0x80) == 0x80 {
             Err(AppError::U14BEMsbSet(bytes[0], bytes[1]))
@@ -66,3 +127,14 @@ impl fmt::Debug for U14BE {
         write!(f, "{} ({:?})", self.host, self.to_device())
     }
 }
+
+#[test]
+fn test_u14be_serde() {
+    let tempo = U14BE::new(120).unwrap();
+    assert_eq!("120\n", serde_yaml::to_string(&tempo).unwrap());
+    assert_eq!(tempo, serde_yaml::from_str::<U14BE>("120").unwrap());
+    assert_eq!(tempo, serde_yaml::from_str::<U14BE>("host: 120").unwrap());
+    assert_eq!(tempo, serde_json::from_str::<U14BE>("{\"host\": 120}").unwrap());
+    assert!(serde_yaml::from_str::<U14BE>("16384").is_err());
+    assert_eq!([0, 120], tempo.to_device().unwrap());
+}
@@ -23,6 +23,9 @@
  *
  */
 
+use std::fmt;
+use std::str::FromStr;
+
 use midir::{Ignore, MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
 use regex::Regex;
 
@@ -36,32 +39,196 @@ macro_rules! append_array {
 
 const DEVICE_NAME: &str = "MPKmini2";
 
-pub fn midi_out_connect() -> Result<MidiOutputConnection, AppError> {
+/// Selects a MIDI port by index (as listed by [`list_ports`]), exact name or regex.
+///
+/// The default matches the first port that looks like an MPK Mini mkII.
+#[derive(Clone, Debug)]
+pub enum PortSelector {
+    Index(usize),
+    Pattern(Regex),
+}
+
+impl Default for PortSelector {
+    fn default() -> Self {
+        PortSelector::Pattern(Regex::new(&format!("{DEVICE_NAME} [0-9]+:[0-9]")).unwrap())
+    }
+}
+
+impl FromStr for PortSelector {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, AppError> {
+        if let Ok(index) = s.parse::<usize>() {
+            return Ok(PortSelector::Index(index));
+        }
+        // Port names may contain regex metacharacters; fall back to a literal match
+        let re = Regex::new(s).or_else(|_| Regex::new(&regex::escape(s)))?;
+        Ok(PortSelector::Pattern(re))
+    }
+}
+
+impl fmt::Display for PortSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PortSelector::Index(index) => write!(f, "#{index}"),
+            PortSelector::Pattern(re) => write!(f, "{re}"),
+        }
+    }
+}
+
+impl PortSelector {
+    pub fn matches(&self, index: usize, port_name: &str) -> bool {
+        match self {
+            PortSelector::Index(i) => *i == index,
+            PortSelector::Pattern(re) => re.as_str() == port_name || re.is_match(port_name),
+        }
+    }
+}
+
+/// Whether a port name looks like an MPK Mini mkII.
+pub fn is_mpk_port_name(port_name: &str) -> bool {
+    PortSelector::default().matches(0, port_name)
+}
+
+/// Names of the available MIDI ports, in index order.
+#[derive(Debug, Default)]
+pub struct MidiPorts {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+}
+
+pub fn list_ports() -> Result<MidiPorts, AppError> {
+    let midi_input = MidiInput::new(env!("CARGO_PKG_NAME"))?;
+    let midi_output = MidiOutput::new(env!("CARGO_PKG_NAME"))?;
+    Ok(MidiPorts {
+        inputs: midi_input
+            .ports()
+            .iter()
+            .map(|port| midi_input.port_name(port))
+            .collect::<Result<_, _>>()?,
+        outputs: midi_output
+            .ports()
+            .iter()
+            .map(|port| midi_output.port_name(port))
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// Input and output ports of a single MPK Mini.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DevicePorts {
+    pub input_index: usize,
+    pub input_name: String,
+    pub output_index: usize,
+    pub output_name: String,
+}
+
+impl DevicePorts {
+    /// Input and output port selectors for this device.
+    pub fn selectors(&self) -> (PortSelector, PortSelector) {
+        (
+            PortSelector::Index(self.input_index),
+            PortSelector::Index(self.output_index),
+        )
+    }
+}
+
+// Pairs each MPK input port with the MPK output port of the same name, or else with the next unused
+// MPK output port (port names differ between input and output on some platforms).
+fn pair_device_ports(ports: &MidiPorts) -> Vec<DevicePorts> {
+    let mut outputs: Vec<(usize, &String)> = ports
+        .outputs
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| is_mpk_port_name(name))
+        .collect();
+
+    let mut devices = Vec::new();
+    for (input_index, input_name) in ports.inputs.iter().enumerate() {
+        if !is_mpk_port_name(input_name) || outputs.is_empty() {
+            continue;
+        }
+        let pos = outputs.iter().position(|(_, name)| *name == input_name).unwrap_or(0);
+        let (output_index, output_name) = outputs.remove(pos);
+        devices.push(DevicePorts {
+            input_index,
+            input_name: input_name.clone(),
+            output_index,
+            output_name: output_name.clone(),
+        });
+    }
+    devices
+}
+
+/// Connected MPK Minis, in input port order.
+pub fn list_devices() -> Result<Vec<DevicePorts>, AppError> {
+    Ok(pair_device_ports(&list_ports()?))
+}
+
+pub fn midi_out_connect(port_selector: &PortSelector) -> Result<(MidiOutputConnection, String), AppError> {
     let midi_output = MidiOutput::new(env!("CARGO_PKG_NAME"))?;
     let name = env!("CARGO_PKG_NAME");
-    let re = Regex::new(&format!("{DEVICE_NAME} [0-9]+:[0-9]")).unwrap();
-    for port in midi_output.ports() {
-        let port_name = midi_output.port_name(&port)?;
-        if re.is_match(port_name.as_str()) {
-            return Ok(midi_output.connect(&port, name)?);
+    for (index, port) in midi_output.ports().iter().enumerate() {
+        let port_name = midi_output.port_name(port)?;
+        if port_selector.matches(index, &port_name) {
+            return Ok((midi_output.connect(port, name)?, port_name));
         }
     }
-    Err(AppError::MidiOutputPortNotFound(name.to_owned()))
+    Err(AppError::MidiOutputPortNotFound(port_selector.to_string()))
 }
 
-pub fn midi_in_connect<F, T: Send>(callback: F, data: T) -> Result<MidiInputConnection<T>, AppError>
+pub fn midi_in_connect<F, T: Send>(
+    port_selector: &PortSelector,
+    callback: F,
+    data: T,
+) -> Result<(MidiInputConnection<T>, String), AppError>
 where
     F: FnMut(u64, &[u8], &mut T) + Send + 'static,
 {
     let mut midi_input = MidiInput::new(env!("CARGO_PKG_NAME"))?;
     midi_input.ignore(Ignore::None);
     let name = env!("CARGO_PKG_NAME");
-    let re = Regex::new(&format!("{DEVICE_NAME} [0-9]+:[0-9]")).unwrap();
-    for port in midi_input.ports() {
-        let port_name = midi_input.port_name(&port)?;
-        if re.is_match(port_name.as_str()) {
-            return Ok(midi_input.connect(&port, name, callback, data)?);
+    for (index, port) in midi_input.ports().iter().enumerate() {
+        let port_name = midi_input.port_name(port)?;
+        if port_selector.matches(index, &port_name) {
+            return Ok((midi_input.connect(port, name, callback, data)?, port_name));
         }
     }
-    Err(AppError::MidiInputPortNotFound(name.to_owned()))
+    Err(AppError::MidiInputPortNotFound(port_selector.to_string()))
+}
+
+#[test]
+fn test_pair_device_ports() {
+    let ports = MidiPorts {
+        inputs: vec![
+            "Midi Through 14:0".into(),
+            "MPKmini2 24:0".into(),
+            "MPKmini2 20:0".into(),
+        ],
+        outputs: vec![
+            "MPKmini2 20:0".into(),
+            "Midi Through 14:0".into(),
+            "MPKmini2 24:0".into(),
+        ],
+    };
+    let devices = pair_device_ports(&ports);
+    assert_eq!(2, devices.len());
+    assert_eq!((1, 2), (devices[0].input_index, devices[0].output_index));
+    assert_eq!((2, 0), (devices[1].input_index, devices[1].output_index));
+}
+
+#[test]
+fn test_port_selector() {
+    let by_index: PortSelector = "1".parse().unwrap();
+    assert!(by_index.matches(1, "anything"));
+    assert!(!by_index.matches(0, "anything"));
+
+    let by_name: PortSelector = "MPKmini2:MPKmini2 MIDI 1 20:0".parse().unwrap();
+    assert!(by_name.matches(3, "MPKmini2:MPKmini2 MIDI 1 20:0"));
+
+    let literal: PortSelector = "Synth (USB".parse().unwrap();
+    assert!(literal.matches(0, "Synth (USB"));
+
+    assert!(is_mpk_port_name("MPKmini2 20:0"));
+    assert!(!is_mpk_port_name("Midi Through:Midi Through Port-0 14:0"));
 }
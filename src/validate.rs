@@ -0,0 +1,199 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+//! Semantic validation of bank descriptors: value ranges the device accepts and consistency
+//! between fields (e.g. knob min <= max).
+
+use std::fmt;
+
+use serde_yaml::Value;
+
+use crate::bankfile::preset_file_format;
+use crate::error::AppError;
+use crate::mpkbank::{MpkBankDescriptor, MpkBankDescriptorFile};
+use crate::preset::{from_migrated, middle_c, migrate, parse_value, PresetFormat, PRESET_MODEL};
+
+/// Highest value of a MIDI data byte (note, CC, program).
+pub const MIDI_DATA_MAX: i64 = 127;
+
+/// Tempo range of the device in BPM.
+pub const TEMPO_MIN: i64 = 30;
+pub const TEMPO_MAX: i64 = 240;
+
+/// Tap tempo taps range of the device.
+pub const TEMPO_TAPS_MIN: i64 = 2;
+pub const TEMPO_TAPS_MAX: i64 = 4;
+
+/// A problem with one field, e.g. `knobs[2].control: value 200 out of range (0..127)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationProblem {
+    /// Field path, in the same form as [`crate::diff::FieldDiff::path`]
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Collects problems while checking fields.
+#[derive(Default)]
+pub(crate) struct Problems(Vec<ValidationProblem>);
+
+impl Problems {
+    pub(crate) fn push(&mut self, path: String, message: String) {
+        self.0.push(ValidationProblem { path, message });
+    }
+
+    pub(crate) fn range(&mut self, path: String, value: i64, min: i64, max: i64) {
+        if !(min..=max).contains(&value) {
+            self.push(path, format!("value {value} out of range ({min}..{max})"));
+        }
+    }
+
+    pub(crate) fn midi_data(&mut self, path: String, value: u8) {
+        self.range(path, value as i64, 0, MIDI_DATA_MAX);
+    }
+
+    pub(crate) fn into_result(self) -> Result<(), AppError> {
+        match self.0.is_empty() {
+            true => Ok(()),
+            false => Err(AppError::ValidationFailed(self.0)),
+        }
+    }
+}
+
+// Whether a problem at `path` concerns the field at `field`, or one of its parts (e.g. `knobs[0]` for
+// `knobs[0].min`, `joystick_x[1]` for `joystick_x`)
+fn covers(field: &str, path: &str) -> bool {
+    let within = |inner: &str, outer: &str| {
+        inner == outer || inner.starts_with(&format!("{outer}.")) || inner.starts_with(&format!("{outer}["))
+    };
+    within(field, path) || within(path, field)
+}
+
+/// Validates only the given fields of `bank_desc` (e.g. those changed by user input), so that other
+/// settings read from the device are kept as they are.
+pub(crate) fn validate_fields(bank_desc: &MpkBankDescriptor, fields: &[String]) -> Result<(), AppError> {
+    match bank_desc.validate() {
+        Err(AppError::ValidationFailed(problems)) => {
+            let problems: Vec<_> = problems
+                .into_iter()
+                .filter(|p| fields.iter().any(|field| covers(field, &p.path)))
+                .collect();
+            match problems.is_empty() {
+                true => Ok(()),
+                false => Err(AppError::ValidationFailed(problems)),
+            }
+        }
+        result => result,
+    }
+}
+
+/// Formats problems for error messages, separated by `; `.
+pub fn format_problems(problems: &[ValidationProblem]) -> String {
+    problems.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+}
+
+/// Validates the settings of a preset (of any supported version).
+///
+/// Returns all problems as [`AppError::ValidationFailed`]; other errors (e.g. a missing field or a
+/// malformed note name) are returned as is, since they stop parsing altogether.
+pub fn validate_preset(value: Value) -> Result<(), AppError> {
+    validate_migrated(value, PresetFormat::Yaml)
+}
+
+/// Validates a preset in `format`, like [`validate_preset`].
+pub fn validate_preset_as(s: &str, format: PresetFormat) -> Result<(), AppError> {
+    validate_migrated(parse_value(s, format)?, format)
+}
+
+/// Validates a preset file in the format of its extension, like [`validate_preset`].
+pub fn validate_file(filename: &str) -> Result<(), AppError> {
+    let format = preset_file_format(filename)?;
+    validate_preset_as(&std::fs::read_to_string(filename)?, format)
+}
+
+fn validate_migrated(value: Value, format: PresetFormat) -> Result<(), AppError> {
+    let mut preset = migrate(value)?;
+    if let Some(model) = preset.get("model").and_then(Value::as_str) {
+        if model != PRESET_MODEL {
+            return Err(AppError::PresetModelUnsupported(model.to_owned()));
+        }
+    }
+    let middle_c = middle_c(&preset)?;
+    let settings = preset
+        .as_mapping_mut()
+        .and_then(|preset| preset.remove("settings"))
+        .ok_or_else(|| AppError::PresetInvalid("missing settings".to_owned()))?;
+    from_migrated::<MpkBankDescriptorFile>(settings, format)?.validate(middle_c)
+}
+
+#[test]
+fn test_validate_preset() {
+    use crate::mpkbank::{Knob, MpkBankDescriptor};
+    use crate::preset::Preset;
+
+    let yaml = Preset::new(MpkBankDescriptor::default()).to_yaml().unwrap();
+    assert!(validate_preset(serde_yaml::from_str(&yaml).unwrap()).is_ok());
+
+    let invalid = yaml
+        .replace("keybed_channel: 1\n", "keybed_channel: 40\n")
+        .replace("tempo_taps: 3\n", "tempo_taps: 9\n");
+    let invalid = invalid.replacen("control: 3\n", "control: 200\n", 1);
+    let problems = match validate_preset(serde_yaml::from_str(&invalid).unwrap()) {
+        Err(AppError::ValidationFailed(problems)) => problems,
+        other => panic!("unexpected {other:?}"),
+    };
+    let paths: Vec<_> = problems.iter().map(|p| p.path.as_str()).collect();
+    assert_eq!(vec!["keybed_channel", "tempo_taps", "knobs[2].control"], paths);
+    assert_eq!("keybed_channel: value 40 out of range (1..16)", problems[0].to_string());
+
+    let bank_desc = MpkBankDescriptor::default()
+        .with_tempo_taps(9)
+        .with_knob(0, Knob::new(1, 100, 20))
+        .unwrap();
+    assert!(matches!(
+        bank_desc.validate(),
+        Err(AppError::ValidationFailed(p)) if p.len() == 2 && p[1].path == "knobs[0]"
+    ));
+    // Values read from the device are encoded as they are
+    let bytes = bank_desc.clone().into_bytes().unwrap();
+    assert_eq!(bank_desc, MpkBankDescriptor::from(&bytes).unwrap());
+
+    // JSON and TOML presets, where enums such as the joysticks are maps
+    let preset = Preset::new(MpkBankDescriptor::default());
+    for format in [PresetFormat::Json, PresetFormat::Toml] {
+        let s = preset.to_string_as(format).unwrap();
+        assert!(validate_preset_as(&s, format).is_ok(), "{s}");
+        let invalid = Preset::new(bank_desc.clone()).to_string_as(format).unwrap();
+        assert!(matches!(
+            validate_preset_as(&invalid, format),
+            Err(AppError::ValidationFailed(p)) if p.len() == 2
+        ));
+    }
+}
@@ -0,0 +1,265 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+//! Property tests of the 108 byte bank descriptor codec and the preset file formats.
+
+use mpk_mini_ctl::mpkbank::{
+    ArpeggiatorMode, ArpeggiatorOctave, ArpeggiatorTimeDivision, ClockSource, Joystick, Knob, MiddleC, MidiChannel,
+    Note, Octave, Pad, PadMode, Swing, Toggle, Transpose, MPK_BANK_DESCRIPTOR_LENGTH,
+};
+use mpk_mini_ctl::preset::PresetFormat;
+use mpk_mini_ctl::{MpkBankDescriptor, Preset, U14BE};
+use proptest::prelude::*;
+use proptest::sample::select;
+
+// Joystick mode followed by two CC bytes, of which the device may leave unused ones set.
+fn joystick_bytes() -> impl Strategy<Value = [u8; 3]> {
+    (0..=2u8, any::<u8>(), any::<u8>()).prop_map(|(mode, c1, c2)| [mode, c1, c2])
+}
+
+/// Device representations accepted by [`MpkBankDescriptor::from`], including values outside the
+/// documented ranges that the device may still report (e.g. tempo taps, knobs with min > max).
+fn bank_bytes() -> impl Strategy<Value = Vec<u8>> {
+    let header = (
+        0..=15u8,      // pad channel
+        0..=15u8,      // keybed channel
+        0..=8u8,       // octave
+        0..=1u8,       // arpeggiator
+        0..=5u8,       // arpeggiator mode
+        0..=7u8,       // arpeggiator time division
+        0..=1u8,       // clock source
+        0..=1u8,       // latch
+        0..=5u8,       // swing
+        any::<u8>(),   // tempo taps
+        0..=0x3fffu16, // tempo
+        0..=3u8,       // arpeggiator octave
+    );
+    let pad = (any::<u8>(), any::<u8>(), any::<u8>(), 0..=1u8)
+        .prop_map(|(note, program, control, mode)| [note, program, control, mode]);
+    let knob = any::<[u8; 3]>();
+    (
+        header,
+        joystick_bytes(),
+        joystick_bytes(),
+        prop::collection::vec(pad, 16),
+        prop::collection::vec(knob, 8),
+        0..=24u8,
+    )
+        .prop_map(|(header, joystick_x, joystick_y, pads, knobs, transpose)| {
+            let (
+                pad_channel,
+                keybed_channel,
+                octave,
+                arp,
+                arp_mode,
+                time_division,
+                clock,
+                latch,
+                swing,
+                taps,
+                tempo,
+                arp_octave,
+            ) = header;
+            let mut bytes = vec![
+                pad_channel,
+                keybed_channel,
+                octave,
+                arp,
+                arp_mode,
+                time_division,
+                clock,
+                latch,
+                swing,
+                taps,
+                (tempo >> 7) as u8,
+                (tempo & 0x7f) as u8,
+                arp_octave,
+            ];
+            bytes.extend(joystick_x);
+            bytes.extend(joystick_y);
+            bytes.extend(pads.concat());
+            bytes.extend(knobs.concat());
+            bytes.push(transpose);
+            bytes
+        })
+}
+
+// Device buffer as written back: as read, except for the unused joystick CC bytes, which are written as zero
+fn written(bytes: &[u8]) -> Vec<u8> {
+    let mut written = bytes.to_vec();
+    for joystick in [13, 16] {
+        let used = bytes[joystick] as usize;
+        written[joystick + 1 + used..joystick + 3].fill(0);
+    }
+    written
+}
+
+fn joystick() -> impl Strategy<Value = Joystick> {
+    prop_oneof![
+        Just(Joystick::Pitchbend),
+        (0..=127u8).prop_map(Joystick::ControlChannel),
+        (0..=127u8, 0..=127u8).prop_map(|(c1, c2)| Joystick::SplitControlChannels(c1, c2)),
+    ]
+}
+
+fn toggle() -> impl Strategy<Value = Toggle> {
+    select(vec![Toggle::Off, Toggle::On])
+}
+
+/// Valid bank descriptors, built through the public constructors.
+fn bank_desc() -> impl Strategy<Value = MpkBankDescriptor> {
+    let settings = (
+        -4..=4i8,
+        -12..=12i8,
+        1..=16u8,
+        1..=16u8,
+        joystick(),
+        joystick(),
+        toggle(),
+        toggle(),
+        select(vec![ClockSource::Internal, ClockSource::External]),
+        2..=4u8,
+        30..=240u16,
+    );
+    let arpeggiator = (
+        select(vec![
+            ArpeggiatorMode::Up,
+            ArpeggiatorMode::Down,
+            ArpeggiatorMode::Exclusive,
+            ArpeggiatorMode::Inclusive,
+            ArpeggiatorMode::Order,
+            ArpeggiatorMode::Random,
+        ]),
+        select(vec![
+            ArpeggiatorTimeDivision::_4,
+            ArpeggiatorTimeDivision::_4T,
+            ArpeggiatorTimeDivision::_8,
+            ArpeggiatorTimeDivision::_8T,
+            ArpeggiatorTimeDivision::_16,
+            ArpeggiatorTimeDivision::_16T,
+            ArpeggiatorTimeDivision::_32,
+            ArpeggiatorTimeDivision::_32T,
+        ]),
+        1..=4u8,
+        select(vec![
+            Swing::_50,
+            Swing::_55,
+            Swing::_57,
+            Swing::_59,
+            Swing::_61,
+            Swing::_64,
+        ]),
+    );
+    let pad = (
+        0..=127u8,
+        0..=127u8,
+        0..=127u8,
+        select(vec![PadMode::Momentary, PadMode::Toggle]),
+    )
+        .prop_map(|(note, control, program, mode)| Pad::new(Note::new(note), control, program, mode));
+    let knob = (0..=127u8, 0..=127u8, 0..=127u8).prop_map(|(control, a, b)| Knob::new(control, a.min(b), a.max(b)));
+    (
+        settings,
+        arpeggiator,
+        prop::array::uniform16(pad),
+        prop::array::uniform8(knob),
+    )
+        .prop_map(|(settings, arpeggiator, pads, knobs)| {
+            let (
+                octave,
+                transpose,
+                pad_channel,
+                keybed_channel,
+                joystick_x,
+                joystick_y,
+                arp,
+                latch,
+                clock,
+                taps,
+                tempo,
+            ) = settings;
+            let (arp_mode, time_division, arp_octave, swing) = arpeggiator;
+            MpkBankDescriptor::default()
+                .with_octave(Octave::new(octave).unwrap())
+                .with_transpose(Transpose::new(transpose).unwrap())
+                .with_pad_midi_channel(MidiChannel::new(pad_channel).unwrap())
+                .with_keybed_channel(MidiChannel::new(keybed_channel).unwrap())
+                .with_joystick_x(joystick_x)
+                .with_joystick_y(joystick_y)
+                .with_arpeggiator(arp)
+                .with_arpeggiator_mode(arp_mode)
+                .with_arpeggiator_time_division(time_division)
+                .with_arpeggiator_octave(ArpeggiatorOctave::new(arp_octave).unwrap())
+                .with_swing(swing)
+                .with_latch(latch)
+                .with_clock_source(clock)
+                .with_tempo_taps(taps)
+                .with_tempo(U14BE::new(tempo).unwrap())
+                .with_pads(pads)
+                .with_knobs(knobs)
+        })
+}
+
+proptest! {
+    #[test]
+    fn bytes_round_trip(bytes in bank_bytes()) {
+        prop_assert_eq!(MPK_BANK_DESCRIPTOR_LENGTH, bytes.len());
+        let bank_desc = MpkBankDescriptor::from(&bytes).unwrap();
+        prop_assert_eq!(written(&bytes), bank_desc.into_bytes().unwrap());
+    }
+
+    #[test]
+    fn bytes_yaml_round_trip(
+        bytes in bank_bytes(),
+        middle_c in select(vec![MiddleC::C3, MiddleC::C4, MiddleC::C5]),
+    ) {
+        // Device buffers, also outside the documented ranges, are read back from presets as they are
+        let bank_desc = MpkBankDescriptor::from(&bytes).unwrap();
+        let yaml = Preset::new(bank_desc.clone()).with_middle_c(middle_c).to_yaml().unwrap();
+        let preset = Preset::from_reader(yaml.as_bytes()).unwrap();
+        prop_assert_eq!(&bank_desc, &preset.settings);
+        prop_assert_eq!(written(&bytes), preset.settings.into_bytes().unwrap());
+    }
+
+    #[test]
+    fn bank_desc_round_trip(bank_desc in bank_desc()) {
+        let bytes = bank_desc.clone().into_bytes().unwrap();
+        prop_assert_eq!(&bank_desc, &MpkBankDescriptor::from(&bytes).unwrap());
+
+        let yaml = serde_yaml::to_string(&bank_desc).unwrap();
+        prop_assert_eq!(&bank_desc, &serde_yaml::from_str::<MpkBankDescriptor>(&yaml).unwrap());
+    }
+
+    #[test]
+    fn preset_round_trip(
+        bank_desc in bank_desc(),
+        middle_c in select(vec![MiddleC::C3, MiddleC::C4, MiddleC::C5]),
+        format in select(vec![PresetFormat::Yaml, PresetFormat::Json, PresetFormat::Toml]),
+    ) {
+        let preset = Preset::new(bank_desc).with_middle_c(middle_c);
+        let serialized = preset.to_string_as(format).unwrap();
+        prop_assert_eq!(preset, Preset::from_str_as(&serialized, format).unwrap());
+    }
+}
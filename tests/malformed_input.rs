@@ -0,0 +1,80 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+//! Parsers must return errors rather than panic on arbitrary input (see also the cargo-fuzz
+//! targets in `fuzz/`).
+
+use mpk_mini_ctl::mpkbank::{MiddleC, MPK_BANK_DESCRIPTOR_LENGTH};
+use mpk_mini_ctl::mpkmidi::MidiStreamParser;
+use mpk_mini_ctl::patch::BankPatch;
+use mpk_mini_ctl::preset::PresetFormat;
+use mpk_mini_ctl::{MpkBankDescriptor, MpkMidiMessage, Preset};
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn parse_msg_no_panic(bytes in prop::collection::vec(any::<u8>(), 0..200)) {
+        let _ = MpkMidiMessage::parse_msg(&bytes);
+    }
+
+    #[test]
+    fn parse_sysex_no_panic(payload in prop::collection::vec(0..0x80u8, 0..120)) {
+        let mut bytes = vec![0xf0, 0x47, 0x00, 0x26, 0x67, 0x00, 0x6d];
+        bytes.extend(payload);
+        bytes.push(0xf7);
+        let _ = MpkMidiMessage::parse_msg(&bytes);
+    }
+
+    #[test]
+    fn stream_parser_no_panic(chunks in prop::collection::vec(prop::collection::vec(any::<u8>(), 0..40), 0..10)) {
+        let mut parser = MidiStreamParser::new();
+        for chunk in chunks {
+            let _ = parser.push(&chunk);
+        }
+        let _ = parser.finish();
+    }
+
+    #[test]
+    fn bank_descriptor_no_panic(bytes in prop::collection::vec(any::<u8>(), MPK_BANK_DESCRIPTOR_LENGTH)) {
+        if let Ok(bank_desc) = MpkBankDescriptor::from(&bytes) {
+            let _ = bank_desc.into_bytes();
+        }
+    }
+
+    #[test]
+    fn preset_no_panic(s in "\\PC*") {
+        for format in [PresetFormat::Yaml, PresetFormat::Json, PresetFormat::Toml] {
+            let _ = Preset::from_str_as(&s, format);
+        }
+        let _ = BankPatch::from_reader(s.as_bytes(), MiddleC::C4);
+    }
+
+    #[test]
+    fn preset_fields_no_panic(octave in any::<i64>(), note in "[A-Gb#♯♭/ 0-9-]{0,12}", tempo in any::<i64>()) {
+        let yaml = format!("octave: {octave}\npads: {{0: {{note: \"{note}\"}}}}\ntempo: {tempo}\n");
+        let _ = Preset::from_str_as(&yaml, PresetFormat::Yaml);
+        let _ = BankPatch::from_reader(yaml.as_bytes(), MiddleC::C4).and_then(|patch| patch.apply(&MpkBankDescriptor::default()));
+    }
+}
@@ -0,0 +1,149 @@
+/*
+ * Copyright 2017 Eldad Zack
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a
+ * copy of this software and associated documentation files (the "Software"),
+ * to deal in the Software without restriction, including without
+ * limitation the rights to use, copy, modify, merge, publish, distribute,
+ * sublicense, and/or sell copies of the Software, and to permit persons to
+ * whom the Software is furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in
+ * all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+ * THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+ * THE SOFTWARE.
+ *
+ * https://opensource.org/licenses/MIT
+ *
+ */
+
+//! Property tests of MIDI message parsing and serialization.
+
+use mpk_mini_ctl::mpkmidi::{DeviceIdentity, MidiStreamParser, SysExId};
+use mpk_mini_ctl::MpkMidiMessage;
+use proptest::prelude::*;
+
+fn data() -> impl Strategy<Value = u8> {
+    0..=127u8
+}
+
+fn sysex_id() -> impl Strategy<Value = SysExId> {
+    prop_oneof![
+        (1..=0x7du8).prop_map(SysExId::Manufacturer),
+        (data(), data()).prop_map(|(id1, id2)| SysExId::Extended(id1, id2)),
+        Just(SysExId::NonRealTime),
+        Just(SysExId::RealTime),
+    ]
+}
+
+/// Any message except banks (covered by the bank codec tests).
+fn message() -> impl Strategy<Value = MpkMidiMessage> {
+    let channel = 0..=15u8;
+    prop_oneof![
+        (channel.clone(), data(), data()).prop_map(|(c, n, v)| MpkMidiMessage::NoteOff(c, n, v)),
+        (channel.clone(), data(), data()).prop_map(|(c, n, v)| MpkMidiMessage::NoteOn(c, n, v)),
+        (channel.clone(), data(), data()).prop_map(|(c, n, p)| MpkMidiMessage::PolyphonicPressure(c, n, p)),
+        (channel.clone(), data(), data()).prop_map(|(c, n, v)| MpkMidiMessage::ControlChange(c, n, v)),
+        (channel.clone(), data()).prop_map(|(c, p)| MpkMidiMessage::ProgramChange(c, p)),
+        (channel.clone(), data()).prop_map(|(c, p)| MpkMidiMessage::ChannelPressure(c, p)),
+        (channel, 0..=0x3fffu16).prop_map(|(c, v)| MpkMidiMessage::PitchBend(c, v)),
+        (0..=7u8, 0..=15u8).prop_map(|(t, v)| MpkMidiMessage::MtcQuarterFrame(t, v)),
+        (0..=0x3fffu16).prop_map(MpkMidiMessage::SongPosition),
+        data().prop_map(MpkMidiMessage::SongSelect),
+        Just(MpkMidiMessage::TuneRequest),
+        Just(MpkMidiMessage::TimingClock),
+        Just(MpkMidiMessage::Start),
+        Just(MpkMidiMessage::Continue),
+        Just(MpkMidiMessage::Stop),
+        Just(MpkMidiMessage::ActiveSensing),
+        Just(MpkMidiMessage::Reset),
+        prop::sample::select(vec![0xf4u8, 0xf5, 0xf9, 0xfd]).prop_map(|status| MpkMidiMessage::Unknown(vec![status])),
+        (sysex_id(), prop::collection::vec(data(), 0..32))
+            // Identity replies are parsed as such
+            .prop_filter("identity reply", |(id, data)| {
+                *id != SysExId::NonRealTime || !matches!(data.as_slice(), [_, 0x06, 0x02, ..])
+            })
+            .prop_map(|(id, data)| MpkMidiMessage::SysEx(id, data)),
+        (
+            data(),
+            sysex_id().prop_filter("manufacturer", |id| {
+                matches!(id, SysExId::Manufacturer(_) | SysExId::Extended(..))
+            }),
+            0..=0x3fffu16,
+            0..=0x3fffu16,
+            [data(), data(), data(), data()],
+        )
+            .prop_map(|(device_id, manufacturer, family, model, firmware)| {
+                MpkMidiMessage::IdentityReply(DeviceIdentity {
+                    device_id,
+                    manufacturer,
+                    family,
+                    model,
+                    firmware,
+                })
+            }),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn message_round_trip(msg in message()) {
+        let bytes = msg.to_bytes().unwrap();
+        prop_assert_eq!(msg, MpkMidiMessage::parse_msg(&bytes).unwrap());
+    }
+
+    #[test]
+    fn stream_round_trip(
+        messages in prop::collection::vec(message(), 0..50),
+        chunk_sizes in prop::collection::vec(1..20usize, 1..10),
+    ) {
+        // Encode with running status: channel messages repeating the previous channel status omit it
+        let mut stream = Vec::new();
+        let mut running_status = None;
+        for msg in &messages {
+            let bytes = msg.to_bytes().unwrap();
+            match bytes[0] {
+                status @ 0x80..=0xef if running_status == Some(status) => stream.extend(&bytes[1..]),
+                status @ 0x80..=0xef => {
+                    running_status = Some(status);
+                    stream.extend(bytes);
+                }
+                // Real time
+                0xf8..=0xff => stream.extend(bytes),
+                _ => {
+                    running_status = None;
+                    stream.extend(bytes);
+                }
+            }
+        }
+
+        let mut parser = MidiStreamParser::new();
+        let mut parsed = Vec::new();
+        let mut rest = stream.as_slice();
+        for size in chunk_sizes.iter().cycle() {
+            if rest.is_empty() {
+                break;
+            }
+            let (chunk, tail) = rest.split_at((*size).min(rest.len()));
+            for result in parser.push(chunk) {
+                parsed.push(result.unwrap());
+            }
+            rest = tail;
+        }
+        prop_assert!(parser.finish().is_ok());
+        prop_assert_eq!(messages, parsed);
+    }
+
+    #[test]
+    fn parsed_bytes_round_trip(bytes in prop::collection::vec(any::<u8>(), 1..4)) {
+        if let Ok(msg) = MpkMidiMessage::parse_msg(&bytes) {
+            prop_assert_eq!(bytes, msg.to_bytes().unwrap());
+        }
+    }
+}